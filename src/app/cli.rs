@@ -1,5 +1,15 @@
-use crate::app::commands::Commands;
+use crate::app::commands::{Commands, PromptAction, SessionAction, TableAction, VersionAction};
+use crate::app::constants::{QUERY_BATCH_SIZE, RERANK_MODEL};
+use crate::chat::agentic::AgenticRetriever;
+use crate::chat::citations::Citations;
+use crate::chat::context_builder::ContextBuilder;
+use crate::chat::prompt_library::{self, PromptSource};
+use crate::chat::prompt_template::PromptTemplate;
+use crate::chat::reranker::{RerankMethod, Reranker};
+use crate::chat::response_format::ResponseFormat;
 use crate::lancevectordb;
+use crate::lancevectordb::load_lancedb::IndexKind;
+use crate::lancevectordb::query::QueryResult;
 use anyhow::Result;
 use anyhow::{Context, Ok};
 use http_body_util::Full;
@@ -11,7 +21,7 @@ use hyper_rustls::HttpsConnector;
 use hyper_util::client::legacy::connect::HttpConnector;
 use hyper_util::client::legacy::Client as LegacyClient;
 use hyper_util::rt::TokioExecutor;
-use log::{debug, info};
+use log::{debug, info, warn};
 use rustls::crypto::ring::default_provider;
 
 pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
@@ -23,13 +33,27 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
             embed_model,
             api_url,
             api_key,
+            distance_type,
+            database,
+            table,
+            append,
+            source,
+            index,
+            tag,
+            tag_git_commit,
+            no_optimize,
+            optimize,
         } => {
+            let target = lancevectordb::LoadTarget::new(&path, database, table, source, append);
             info!("Using the Load arguments below:");
             info!(" Path: {:?}", path);
             info!(" Chunk Size: {:?}", chunk_size);
             info!(" LLM Provider: {:?}", llm_provider);
             info!(" Embedding Model: {:?}", embed_model);
             info!(" API URL: {:?}", api_url);
+            info!(" Distance Type: {}", distance_type);
+            info!(" Target: {:?}", target);
+            info!(" Index: {:?}", index);
 
             let https_client = get_https_client().context("Failed to create HTTPS client")?;
             // let embed_url = format!("{}/{}", constants::CHAT_API_URL, "api/embed");
@@ -46,22 +70,132 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
             // ))
             // .context("Failed to check client")?;
 
-            rt.block_on(lancevectordb::run_embedding_pipeline(
-                &path,
-                chunk_size,
-                llm_provider.as_str(),
-                &api_url,
-                &api_key,
-                embed_model.as_str(),
-                &https_client,
-            ))
-            .context("Failed to run lancevectordb")?;
+            let chunks = rt
+                .block_on(lancevectordb::run_embedding_pipeline(
+                    &path,
+                    chunk_size,
+                    llm_provider.as_str(),
+                    &api_url,
+                    &api_key,
+                    embed_model.as_str(),
+                    &https_client,
+                    distance_type,
+                    &index.index_params(),
+                    &target,
+                ))
+                .context("Failed to run lancevectordb")?;
+
+            let database = lancevectordb::database_location(&target.database);
+            let entry = lancevectordb::registry::SourceEntry {
+                source: target.source.clone(),
+                path: std::path::absolute(&path)
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|_| path.clone()),
+                database: database.clone(),
+                table: target.table.clone(),
+                model: embed_model.clone(),
+                chunks,
+                loaded_at: chrono::Utc::now().to_rfc3339(),
+            };
+            rt.block_on(lancevectordb::registry::record_source(entry, target.append))
+                .context("Failed to record the loaded source")?;
+
+            println!(
+                "Loaded {} chunks of {} into table {} of database {}",
+                chunks, target.source, target.table, database
+            );
+            println!("Query it with: -d {} -t {}", target.database, target.table);
+
+            if !no_optimize && chunks > 0 {
+                let db = rt
+                    .block_on(lancevectordb::connect(&target.database))
+                    .context("Failed to connect to the database")?;
+                let report = rt
+                    .block_on(lancevectordb::optimize::optimize_table(
+                        &db,
+                        &target.table,
+                        &optimize.optimize_params(),
+                    ))
+                    .context("Failed to optimize the loaded table")?;
+                print_optimize_report(&report);
+            }
+
+            let tag = match tag {
+                Some(tag) => Some(tag),
+                None if tag_git_commit => {
+                    let commit = lancevectordb::versions::git_commit(&path);
+                    if commit.is_none() {
+                        warn!(
+                            "{} is not in a git checkout, the loaded version is not tagged",
+                            path
+                        );
+                    }
+                    commit
+                }
+                None => None,
+            };
+            if let Some(tag) = tag {
+                let db = rt
+                    .block_on(lancevectordb::connect(&target.database))
+                    .context("Failed to connect to the database")?;
+                let version = rt
+                    .block_on(lancevectordb::versions::tag_version(
+                        &db,
+                        &target.table,
+                        &tag,
+                        None,
+                    ))
+                    .context("Failed to tag the loaded version")?;
+                println!("Tagged version {} as {}", version, tag);
+            }
 
             // shutdown the runtime after the embedding is done
             rt.shutdown_timeout(std::time::Duration::from_secs(1));
         }
+        Commands::Optimize {
+            database,
+            table,
+            optimize,
+        } => {
+            let db = rt
+                .block_on(lancevectordb::connect(&database))
+                .context("Failed to connect to the database")?;
+            let report = rt
+                .block_on(lancevectordb::optimize::optimize_table(
+                    &db,
+                    &table,
+                    &optimize.optimize_params(),
+                ))
+                .context("Failed to optimize the table")?;
+            print_optimize_report(&report);
+        }
+        Commands::Reindex {
+            database,
+            table,
+            distance_type,
+            index,
+        } => {
+            let mut db = rt
+                .block_on(lancevectordb::connect(&database))
+                .context("Failed to connect to the database")?;
+            let kind = rt
+                .block_on(lancevectordb::load_lancedb::reindex_table(
+                    &mut db,
+                    &table,
+                    distance_type,
+                    &index.index_params(),
+                ))
+                .context("Failed to rebuild the vector index")?;
+            match kind {
+                IndexKind::Flat => println!("Table {} is left without a vector index", table),
+                kind => println!("Rebuilt the {} index of table {}", kind, table),
+            }
+        }
         Commands::LanceQuery {
             input,
+            fuse,
+            batch,
+            output,
             llm_provider,
             api_url,
             api_key,
@@ -70,14 +204,20 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
             database,
             whole_query,
             file_context,
+            search,
         } => {
-            let input_list = Commands::fetch_prompt_from_cli(input.clone(), "Enter query: ");
             // let embed_model = model.to_string();
             // let vector_table = table.to_string();
             // let db_uri = database.to_string();
             let whole_query: bool = whole_query
                 .parse()
                 .context("Failed to parse whole_query flag")?;
+            // the whole table query exports the table and needs no query
+            let input_list = match &batch {
+                Some(batch) => read_batch_queries(batch)?,
+                None if whole_query => input.clone(),
+                None => Commands::fetch_prompt_from_cli(input.clone(), "Enter query: "),
+            };
             let file_context: bool = file_context
                 .parse()
                 .context("Failed to parse file_query flag")?;
@@ -89,32 +229,71 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
             info!(" Table: {:?}", table);
             info!(" Whole Query: {:?}", whole_query);
             info!(" File Query: {:?}", file_context);
+            info!(" Search: {:?}", search);
 
             // Initialize the http client outside the thread // TODO wrap in Arc<Mutex>
             let https_client = get_https_client().context("Failed to create HTTPS client")?;
 
             // Initialize the database
             let mut db = rt
-                .block_on(lancedb::connect(&database).execute())
+                .block_on(lancevectordb::connect(&database))
                 .context("Failed to connect to the database")?;
 
-            // Query the database
-            let content = rt
-                .block_on(lancevectordb::query::run_query(
-                    &mut db,
-                    llm_provider.as_str(),
-                    api_url.as_str(),
-                    api_key.as_str(),
-                    model.as_str(),
-                    &input_list,
-                    &table,
-                    &https_client,
-                    whole_query,
-                    file_context,
-                ))
-                .context("Failed to run query")?;
+            let query_params = search
+                .query_params()
+                .context("Failed to parse search parameters")?;
+
+            // Query the database, a batch file is embedded a few queries at a time
+            let mut results = Vec::new();
+            if whole_query {
+                let records = rt
+                    .block_on(lancevectordb::query::query_whole_table(
+                        &db,
+                        &table,
+                        &query_params,
+                    ))
+                    .context("Failed to run query")?;
+                results.push(QueryResult {
+                    query: input_list.join(" | "),
+                    records,
+                });
+            } else {
+                for inputs in input_list.chunks(QUERY_BATCH_SIZE) {
+                    let batch_results = rt
+                        .block_on(lancevectordb::query::run_queries(
+                            &mut db,
+                            llm_provider.as_str(),
+                            api_url.as_str(),
+                            api_key.as_str(),
+                            model.as_str(),
+                            inputs,
+                            &table,
+                            &https_client,
+                            whole_query,
+                            file_context,
+                            &query_params,
+                        ))
+                        .context("Failed to run query")?;
+                    results.extend(batch_results);
+                }
+            }
 
-            println!("Query Response: {:?}", content);
+            if fuse && results.len() > 1 {
+                results = vec![QueryResult {
+                    query: input_list.join(" | "),
+                    records: lancevectordb::query::fuse_results(&results),
+                }];
+            }
+
+            if batch.is_some() || output.is_some() {
+                write_json_lines(&results, output.as_deref())?;
+            } else {
+                for result in &results {
+                    let content: Vec<&String> = result.records.iter().map(|r| &r.content).collect();
+                    println!("Query: {}", result.query);
+                    println!("Query Response: {:?}", content);
+                }
+            }
         }
         Commands::RagQuery {
             input,
@@ -128,6 +307,17 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
             whole_query,
             file_context,
             system_prompt,
+            template,
+            tools,
+            max_tool_rounds,
+            agentic,
+            max_steps,
+            verbose,
+            show_uncited,
+            format,
+            generation,
+            search,
+            rerank,
         } => {
             let input_list = Commands::fetch_prompt_from_cli(input.clone(), "Enter query: ");
             // let embed_model = embed_model.to_string();
@@ -150,18 +340,51 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
             println!(" AI Model: {:?}", ai_model);
             println!(" Table: {:?}", table);
 
+            // Validate the template before querying so errors show up early
+            let prompt_template = template
+                .as_deref()
+                .map(PromptTemplate::load)
+                .transpose()
+                .context("Failed to load prompt template")?;
+            let format =
+                ResponseFormat::parse(&format).context("Failed to parse response format")?;
+            let options = crate::chat::model_options::resolve_options(
+                &ai_model,
+                generation.options_file.as_deref(),
+                &generation.cli_options(),
+            )
+            .context("Failed to resolve model options")?;
+            let query_params = search
+                .query_params()
+                .context("Failed to parse search parameters")?;
+
             // Initialize the http client outside the thread // TODO wrap in Arc<Mutex>
             let https_client = get_https_client().context("Failed to create HTTPS client")?;
             // do a check to see if client is up
 
             // Initialize the database
             let mut db = rt
-                .block_on(lancedb::connect(&database).execute())
+                .block_on(lancevectordb::connect(&database))
                 .context("Failed to connect to the database")?;
 
-            // Query the database
-            let content = rt
-                .block_on(lancevectordb::query::run_query(
+            // Query the database, the agentic mode lets the model plan the sub-queries
+            let content = if agentic {
+                let retriever = AgenticRetriever::new(
+                    db.clone(),
+                    &table,
+                    llm_provider.as_str(),
+                    &api_url,
+                    &api_key,
+                    &embed_model,
+                    &ai_model,
+                )
+                .with_max_steps(max_steps)
+                .with_verbose(verbose)
+                .with_query_params(query_params.clone());
+                rt.block_on(retriever.retrieve(input_list.first().unwrap(), &https_client))
+                    .context("Failed to run agentic retrieval")?
+            } else {
+                rt.block_on(lancevectordb::query::run_query(
                     &mut db,
                     llm_provider.as_str(),
                     api_url.as_str(),
@@ -172,12 +395,65 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
                     &https_client,
                     whole_query,
                     file_context,
+                    &query_params,
                 ))
-                .context("Failed to run query")?;
+                .context("Failed to run query")?
+            };
 
             debug!("Query Response: {:?}", content);
 
-            let context = content.join(" ");
+            // score the nearest chunks against the question and keep the best
+            let content = match rerank.rerank {
+                Some(method) => {
+                    let rerank_model =
+                        rerank.rerank_model.clone().unwrap_or_else(|| match method {
+                            RerankMethod::Api => RERANK_MODEL.to_string(),
+                            RerankMethod::Llm => ai_model.clone(),
+                        });
+                    let reranker = Reranker::new(
+                        method,
+                        llm_provider.as_str(),
+                        rerank.rerank_url.as_deref().unwrap_or(&api_url),
+                        &api_key,
+                        &rerank_model,
+                    )
+                    .with_candidates(rerank.rerank_candidates)
+                    .with_top_k(rerank.rerank_top_k);
+                    rt.block_on(reranker.rerank(
+                        input_list.first().unwrap(),
+                        &content,
+                        &https_client,
+                    ))
+                    .context("Failed to rerank the retrieved chunks")?
+                }
+                None => content,
+            };
+
+            // drop the chunk overlaps, merge adjacent chunks and keep what fits the model context
+            let blocks =
+                ContextBuilder::new(crate::chat::context_token_budget(&options)).build(&content);
+            let content: Vec<_> = blocks.iter().flatten().cloned().collect();
+            let citations = Citations::from_blocks(&blocks).with_show_uncited(show_uncited);
+            let repository = database.trim_end_matches('/').trim_end_matches("_db");
+            let prompt_template = prompt_template.map(|t| {
+                t.with_repository(lancevectordb::get_file_name(repository).as_str())
+                    .with_chunks(&content)
+            });
+            // number the sources so the answer can cite them
+            let context = citations.context();
+            let tool_context = tools.then(|| {
+                crate::chat::tools::ToolContext::new(
+                    db.clone(),
+                    &table,
+                    llm_provider.as_str(),
+                    &api_url,
+                    &api_key,
+                    &embed_model,
+                    &https_client,
+                    max_tool_rounds,
+                )
+                .with_query_params(query_params.clone())
+            });
             // @ TODO: make this a command line argument
             // let system_prompt = "template/rag_prompt.txt";
             // let system_prompt = "template/software-engineer.txt";
@@ -187,6 +463,12 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
                 system_prompt.as_str(),
                 input_list.first().unwrap(),
                 Some(&context),
+                Some(&citations),
+                None,
+                prompt_template.as_ref(),
+                &format,
+                &options,
+                tool_context.as_ref(),
                 &https_client,
                 llm_provider.as_str(),
                 &api_url,
@@ -203,6 +485,10 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
             api_url,
             api_key,
             ai_model,
+            system_prompt,
+            template,
+            format,
+            generation,
         } => {
             // let prompt = Commands::fetch_prompt_from_cli(Vec::new(), "Enter prompt: ");
             println!("Chat command is run with below arguments:");
@@ -212,14 +498,30 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
             println!(" API Key: {:?}", api_key);
             println!(" AI Model: {:?}", ai_model);
 
+            let prompt_template = template
+                .as_deref()
+                .map(PromptTemplate::load)
+                .transpose()
+                .context("Failed to load prompt template")?;
+            let format =
+                ResponseFormat::parse(&format).context("Failed to parse response format")?;
+            let options = crate::chat::model_options::resolve_options(
+                &ai_model,
+                generation.options_file.as_deref(),
+                &generation.cli_options(),
+            )
+            .context("Failed to resolve model options")?;
+
             let context: Option<&str> = None;
             let client = get_https_client().context("Failed to create HTTPS client")?;
 
-            let system_prompt = "template/general_prompt.txt";
             rt.block_on(crate::chat::run_chat(
-                system_prompt,
+                system_prompt.as_str(),
                 &prompt,
                 context,
+                prompt_template.as_ref(),
+                &format,
+                &options,
                 &client,
                 llm_provider.as_str(),
                 &api_url,
@@ -233,11 +535,391 @@ pub fn cli(commands: Commands, rt: tokio::runtime::Runtime) -> Result<()> {
         Commands::Version { version } => {
             info!("Version: {}", version);
         }
+        Commands::Sessions { action } => match action {
+            SessionAction::List => {
+                let sessions = rt
+                    .block_on(crate::chat::session::list_sessions())
+                    .context("Failed to list sessions")?;
+                if sessions.is_empty() {
+                    println!("No saved sessions");
+                }
+                for session in sessions {
+                    println!(
+                        "{}  turns: {:<3} model: {:<20} updated: {}  {}",
+                        session.id,
+                        session.turns,
+                        session.model,
+                        session.updated_at,
+                        session.first_question
+                    );
+                }
+            }
+            SessionAction::Resume {
+                id,
+                llm_provider,
+                api_url,
+                api_key,
+                ai_model,
+                system_prompt,
+                format,
+                generation,
+            } => {
+                let format =
+                    ResponseFormat::parse(&format).context("Failed to parse response format")?;
+                let options = crate::chat::model_options::resolve_options(
+                    &ai_model,
+                    generation.options_file.as_deref(),
+                    &generation.cli_options(),
+                )
+                .context("Failed to resolve model options")?;
+                let prompt = Commands::fetch_args_from_cli(String::new(), "Ask Followup: ");
+                let client = get_https_client().context("Failed to create HTTPS client")?;
+
+                rt.block_on(crate::chat::run_chat_with_history(
+                    system_prompt.as_str(),
+                    &prompt,
+                    None,
+                    None,
+                    Some(id.as_str()),
+                    None,
+                    &format,
+                    &options,
+                    None,
+                    &client,
+                    llm_provider.as_str(),
+                    &api_url,
+                    &api_key,
+                    &ai_model,
+                ))
+                .context("Failed to resume chat")?;
+
+                rt.shutdown_timeout(std::time::Duration::from_secs(1));
+            }
+            SessionAction::Delete { id } => {
+                rt.block_on(crate::chat::session::delete_session(&id))
+                    .context("Failed to delete session")?;
+                println!("Deleted session {}", id);
+            }
+            SessionAction::Export { id, output } => {
+                let markdown = rt
+                    .block_on(crate::chat::session::export_markdown(&id))
+                    .context("Failed to export session")?;
+                match output {
+                    Some(path) => {
+                        std::fs::write(&path, markdown)
+                            .with_context(|| format!("Failed to write {}", path))?;
+                        println!("Exported session {} to {}", id, path);
+                    }
+                    None => println!("{}", markdown),
+                }
+            }
+        },
+        Commands::Prompts { action } => match action {
+            PromptAction::List => {
+                let prompts = prompt_library::list_prompts().context("Failed to list prompts")?;
+                for prompt in prompts {
+                    match prompt.source {
+                        PromptSource::BuiltIn => println!("{:<25} built-in", prompt.name),
+                        PromptSource::User(path) => {
+                            println!("{:<25} {}", prompt.name, path.display())
+                        }
+                    }
+                }
+            }
+            PromptAction::Show { name } => {
+                let prompt = rt
+                    .block_on(prompt_library::load_prompt(&name))
+                    .context("Failed to load prompt")?;
+                println!("{}", prompt);
+            }
+        },
+        Commands::Tables { action } => run_table_action(action, &rt)?,
+        Commands::Versions { action } => run_version_action(action, &rt)?,
     }
 
     Ok(())
 }
 
+/// Run a table management action, dropping and renaming ask for confirmation
+fn run_table_action(action: TableAction, rt: &tokio::runtime::Runtime) -> Result<()> {
+    let connect = |database: &str| {
+        rt.block_on(lancevectordb::connect(database))
+            .context("Failed to connect to the database")
+    };
+    match action {
+        TableAction::List { database } => {
+            let db = connect(&database)?;
+            let tables = rt.block_on(lancevectordb::tables::list_tables(&db))?;
+            if tables.is_empty() {
+                println!("No tables in {}", database);
+            }
+            for table in tables {
+                println!("{}", table);
+            }
+        }
+        TableAction::Describe { database, table } => {
+            let db = connect(&database)?;
+            let description = rt.block_on(lancevectordb::tables::describe_table(&db, &table))?;
+            println!(
+                "Table: {}  version: {}  uri: {}",
+                description.name, description.version, description.uri
+            );
+            println!("Columns:");
+            for column in &description.columns {
+                let nullable = if column.nullable { "null" } else { "not null" };
+                println!(
+                    "  {:<15} {:<40} {}",
+                    column.name, column.data_type, nullable
+                );
+            }
+            if !description.metadata.is_empty() {
+                println!("Metadata:");
+                let mut metadata: Vec<_> = description.metadata.iter().collect();
+                metadata.sort();
+                for (key, value) in metadata {
+                    println!("  {} = {}", key, value);
+                }
+            }
+            println!("Indices:");
+            if description.indices.is_empty() {
+                println!("  none");
+            }
+            for index in &description.indices {
+                let distance = index
+                    .distance_type
+                    .map(|d| format!("  distance: {}", d))
+                    .unwrap_or_default();
+                println!(
+                    "  {:<25} {:<12} on {:<15} indexed: {:<8} unindexed: {}{}",
+                    index.name,
+                    index.index_type.to_string(),
+                    index.columns.join(", "),
+                    index
+                        .indexed_rows
+                        .map_or("-".to_string(), |r| r.to_string()),
+                    index
+                        .unindexed_rows
+                        .map_or("-".to_string(), |r| r.to_string()),
+                    distance
+                );
+            }
+        }
+        TableAction::Stats { database, table } => {
+            let db = connect(&database)?;
+            let stats = rt.block_on(lancevectordb::tables::table_stats(&db, &table))?;
+            let time = |t: Option<chrono::DateTime<chrono::Utc>>| {
+                t.map_or("-".to_string(), |t| t.to_rfc3339())
+            };
+            println!("Table:      {}", stats.name);
+            println!("Rows:       {}", stats.rows);
+            println!("Files:      {}", stats.files);
+            println!("Models:     {}", stats.models.join(", "));
+            println!(
+                "Dimension:  {}",
+                stats.dimension.map_or("-".to_string(), |d| d.to_string())
+            );
+            println!(
+                "Created:    {} to {}",
+                time(stats.first_created),
+                time(stats.last_created)
+            );
+            println!(
+                "Size:       {}",
+                stats.size_bytes.map_or("-".to_string(), format_size)
+            );
+        }
+        TableAction::Drop {
+            database,
+            table,
+            yes,
+        } => {
+            let db = connect(&database)?;
+            if !Commands::confirm_from_cli(yes, &format!("Drop table {} of {}?", table, database)) {
+                println!("Table {} was not dropped", table);
+                return Ok(());
+            }
+            rt.block_on(lancevectordb::tables::drop_table(&db, &table))?;
+            rt.block_on(lancevectordb::registry::forget_table(
+                &lancevectordb::database_location(&database),
+                &table,
+            ))?;
+            println!("Dropped table {}", table);
+        }
+        TableAction::Rename {
+            database,
+            table,
+            new_name,
+            yes,
+        } => {
+            let db = connect(&database)?;
+            if !Commands::confirm_from_cli(
+                yes,
+                &format!("Rename table {} of {} to {}?", table, database, new_name),
+            ) {
+                println!("Table {} was not renamed", table);
+                return Ok(());
+            }
+            rt.block_on(lancevectordb::tables::rename_table(&db, &table, &new_name))?;
+            rt.block_on(lancevectordb::registry::rename_table(
+                &lancevectordb::database_location(&database),
+                &table,
+                &new_name,
+            ))?;
+            println!("Renamed table {} to {}", table, new_name);
+        }
+        TableAction::Cleanup { database, table } => {
+            let db = connect(&database)?;
+            let rows = rt.block_on(lancevectordb::tables::remove_placeholder_rows(&db, &table))?;
+            println!("Removed {} placeholder rows from table {}", rows, table);
+            if rows > 0 {
+                println!(
+                    "Rebuild the vector index with: reindex -d {} -t {}",
+                    database, table
+                );
+            }
+        }
+        TableAction::Sources { database } => {
+            let database = database.map(|d| lancevectordb::database_location(&d));
+            let sources =
+                rt.block_on(lancevectordb::registry::list_sources(database.as_deref()))?;
+            if sources.is_empty() {
+                println!("No loaded sources");
+            }
+            for source in sources {
+                println!(
+                    "{:<20} chunks: {:<6} model: {:<20} loaded: {}  {} -> {} of {}",
+                    source.source,
+                    source.chunks,
+                    source.model,
+                    source.loaded_at,
+                    source.path,
+                    source.table,
+                    source.database
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run a table version action, restoring asks for confirmation
+fn run_version_action(action: VersionAction, rt: &tokio::runtime::Runtime) -> Result<()> {
+    let connect = |database: &str| {
+        rt.block_on(lancevectordb::connect(database))
+            .context("Failed to connect to the database")
+    };
+    match action {
+        VersionAction::List { database, table } => {
+            let db = connect(&database)?;
+            let versions = rt.block_on(lancevectordb::versions::list_versions(&db, &table))?;
+            println!("{:<8} {:<25} Tags", "Version", "Created");
+            for version in versions {
+                let latest = if version.latest { " (latest)" } else { "" };
+                println!(
+                    "{:<8} {:<25} {}{}",
+                    version.version,
+                    version.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+                    version.tags.join(", "),
+                    latest
+                );
+            }
+        }
+        VersionAction::Tag {
+            database,
+            table,
+            name,
+            table_version,
+        } => {
+            let db = connect(&database)?;
+            let version = rt.block_on(lancevectordb::versions::tag_version(
+                &db,
+                &table,
+                &name,
+                table_version,
+            ))?;
+            println!("Tagged version {} of {} as {}", version, table, name);
+        }
+        VersionAction::Untag {
+            database,
+            table,
+            name,
+        } => {
+            let db = connect(&database)?;
+            let version =
+                rt.block_on(lancevectordb::versions::untag_version(&db, &table, &name))?;
+            println!("Removed tag {} of version {} of {}", name, version, table);
+        }
+        VersionAction::Restore {
+            database,
+            table,
+            table_version,
+            tag,
+            yes,
+        } => {
+            let snapshot = lancevectordb::versions::Snapshot::from_args(table_version, tag)?;
+            let db = connect(&database)?;
+            if !Commands::confirm_from_cli(
+                yes,
+                &format!("Restore {} of table {}?", snapshot, table),
+            ) {
+                println!("Table {} was not restored", table);
+                return Ok(());
+            }
+            let version = rt.block_on(lancevectordb::versions::restore_version(
+                &db, &table, &snapshot,
+            ))?;
+            println!("Restored {} of {} as version {}", snapshot, table, version);
+        }
+    }
+    Ok(())
+}
+
+/// Format a byte count with binary units
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Print the fragments, versions and disk usage of a table before and after it was optimized
+fn print_optimize_report(report: &lancevectordb::optimize::OptimizeReport) {
+    let fragments = |count: Option<usize>| count.map_or("-".to_string(), |c| c.to_string());
+    let size = |bytes: Option<u64>| bytes.map_or("-".to_string(), format_size);
+    println!("Optimized table {}", report.name);
+    println!(
+        "  Fragments: {} -> {} ({} compacted into {})",
+        fragments(report.before.fragments),
+        fragments(report.after.fragments),
+        report.fragments_removed,
+        report.fragments_added
+    );
+    println!(
+        "  Versions:  {} -> {} ({} pruned)",
+        report.before.versions, report.after.versions, report.versions_removed
+    );
+    println!(
+        "  Disk:      {} -> {} ({} removed)",
+        size(report.before.size_bytes),
+        size(report.after.size_bytes),
+        format_size(report.bytes_removed)
+    );
+    if report.tagged_versions > 0 {
+        println!(
+            "  Tags:      {} tagged versions kept, remove tags with versions untag to prune them",
+            report.tagged_versions
+        );
+    }
+}
+
 async fn check_connection(client: &HttpsClient, url: &str) -> Result<()> {
     // let uri = hyper::Uri::from_static(&url);
     let uri = url.parse::<http::Uri>()?;
@@ -254,6 +936,39 @@ async fn check_connection(client: &HttpsClient, url: &str) -> Result<()> {
     Ok(())
 }
 
+/// Read the queries of a batch file, one query per line, empty lines are skipped
+fn read_batch_queries(path: &str) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read batch file: {}", path))?;
+    let queries: Vec<String> = content
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    if queries.is_empty() {
+        anyhow::bail!("No queries found in batch file: {}", path);
+    }
+    Ok(queries)
+}
+
+/// Write every query result as one JSON line to the file or stdout
+fn write_json_lines(results: &[QueryResult], output: Option<&str>) -> Result<()> {
+    let mut lines = String::new();
+    for result in results {
+        lines.push_str(&serde_json::to_string(result).context("Failed to serialize results")?);
+        lines.push('\n');
+    }
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, lines).with_context(|| format!("Failed to write {}", path))?;
+            info!("Wrote {} query results to {}", results.len(), path);
+        }
+        None => print!("{}", lines),
+    }
+    Ok(())
+}
+
 type HttpsClient = LegacyClient<HttpsConnector<HttpConnector>, Full<Bytes>>;
 fn get_https_client() -> Result<HttpsClient> {
     // Install the crypto provider required by rustls
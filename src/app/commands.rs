@@ -1,10 +1,23 @@
 use ::std::io::{self, Write};
 
-use crate::app::constants::{AI_MODEL, EMBEDDING_MODEL, SYSTEM_PROMPT_PATH, VERSION};
+use crate::app::constants::{AI_MODEL, EMBEDDING_MODEL, GENERAL_PROMPT, SYSTEM_PROMPT, VERSION};
 use clap::{Parser, Subcommand, ValueEnum};
+use lancedb::DistanceType;
 use log::info;
 
-use super::constants::{CHAT_API_KEY, CHAT_API_URL};
+use crate::chat::model_options::{Options, OptionsBuilder};
+use crate::chat::reranker::RerankMethod;
+use crate::lancevectordb::filter::SearchFilter;
+use crate::lancevectordb::load_lancedb::{IndexKind, IndexParams};
+use crate::lancevectordb::optimize::OptimizeParams;
+use crate::lancevectordb::query::QueryParams;
+use crate::lancevectordb::versions::Snapshot;
+
+use super::constants::{
+    AGENTIC_MAX_STEPS, CHAT_API_KEY, CHAT_API_URL, CHAT_MAX_TOOL_ROUNDS, CHAT_RESPONSE_FORMAT,
+    INDEX_MIN_ROWS, LANCEDB_DISTANCE_FN, OPTIMIZE_RETENTION_DAYS, QUERY_LIMIT, QUERY_NPROBES,
+    QUERY_REFINE_FACTOR, RERANK_CANDIDATES, RERANK_TOP_K,
+};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -53,12 +66,75 @@ pub enum Commands {
         #[clap(short = 'k', long)]
         #[clap(default_value = CHAT_API_KEY)]
         api_key: String,
+        /// The distance metric of the vector index, l2, cosine or dot
+        #[clap(long, value_parser = parse_distance_type)]
+        #[clap(default_value_t = LANCEDB_DISTANCE_FN)]
+        distance_type: DistanceType,
+        /// Provide the database to load into, <dir name>_db if not provided
+        #[clap(short, long)]
+        database: Option<String>,
+        /// Provide the table to load into, <dir name>_table if not provided
+        #[clap(short, long)]
+        table: Option<String>,
+        /// Add the directory to the table instead of recreating it, a source loaded again is replaced
+        #[clap(long)]
+        append: bool,
+        /// The name of the source stored with the rows, the directory name if not provided
+        #[clap(long)]
+        source: Option<String>,
+        #[clap(flatten)]
+        index: IndexArgs,
+        /// Tag the loaded version of the table, tagged versions and the versions after them are not pruned
+        #[clap(long)]
+        tag: Option<String>,
+        /// Tag the loaded version with the git commit of the path
+        #[clap(long, conflicts_with = "tag")]
+        tag_git_commit: bool,
+        /// Do not compact and prune the table after loading
+        #[clap(long)]
+        no_optimize: bool,
+        #[clap(flatten)]
+        optimize: OptimizeArgs,
+    },
+    /// Compact the fragments of a table, prune its old versions and update its indices
+    Optimize {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to optimize
+        #[clap(short, long)]
+        table: String,
+        #[clap(flatten)]
+        optimize: OptimizeArgs,
+    },
+    /// Rebuild the vector index of a table
+    Reindex {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to reindex
+        #[clap(short, long)]
+        table: String,
+        /// The distance metric of the vector index, the metric of the current index if not provided
+        #[clap(long, value_parser = parse_distance_type)]
+        distance_type: Option<DistanceType>,
+        #[clap(flatten)]
+        index: IndexArgs,
     },
     /// Query the Lance Vector Database
     LanceQuery {
-        /// The query string to use
+        /// The query string to use, can be repeated to search several queries
         #[clap(short, long)]
         input: Vec<String>,
+        /// Fuse the results of several queries into one ranking instead of reporting each query
+        #[clap(long)]
+        fuse: bool,
+        /// Read the queries from a file, one query per line, results are written as JSON lines
+        #[clap(long)]
+        batch: Option<String>,
+        /// Write the results as JSON lines to this file instead of stdout
+        #[clap(long)]
+        output: Option<String>,
         /// Provide the provider to use for query embedding
         #[clap(short = 'p', long)]
         #[clap(default_value = "ollama")]
@@ -81,7 +157,7 @@ pub enum Commands {
         /// Provide the database to use
         #[clap(short, long)]
         database: String,
-        /// specify if the whole table query is to be used default is false
+        /// specify if the whole table query is to be used default is false, every row is read and no query is needed
         #[clap(short, long)]
         #[clap(default_value = "false")]
         whole_query: String,
@@ -89,6 +165,8 @@ pub enum Commands {
         #[clap(short, long)]
         #[clap(default_value = "false")]
         file_context: String,
+        #[clap(flatten)]
+        search: SearchArgs,
     },
     /// Query the Lance Vector Database and chat with the AI
     RagQuery {
@@ -129,32 +207,482 @@ pub enum Commands {
         #[clap(short, long)]
         #[clap(default_value = "false")]
         file_context: String,
-        /// specify if the system prompt is to be used default is false
-        #[clap(short, long)]
-        #[clap(default_value = SYSTEM_PROMPT_PATH)]
+        /// Provide the system prompt, a built-in prompt name like software-engineer or a file path
+        #[clap(short, long, alias = "prompt")]
+        #[clap(default_value = SYSTEM_PROMPT)]
         system_prompt: String,
+        /// Provide a Handlebars template file to render the question and retrieved chunks
+        #[clap(long)]
+        template: Option<String>,
+        /// Let the model search the code, read files and list files to pull more context
+        #[clap(long)]
+        tools: bool,
+        /// The maximum number of tool call rounds before the model has to answer
+        #[clap(long)]
+        #[clap(default_value_t = CHAT_MAX_TOOL_ROUNDS)]
+        max_tool_rounds: usize,
+        /// Let the model split the question into sub-queries and search until it has enough context
+        #[clap(long)]
+        agentic: bool,
+        /// The maximum number of planning steps in agentic mode
+        #[clap(long)]
+        #[clap(default_value_t = AGENTIC_MAX_STEPS)]
+        max_steps: usize,
+        /// Print the sub-queries and results of every agentic step
+        #[clap(long)]
+        verbose: bool,
+        /// List the retrieved sources the answer does not cite
+        #[clap(long)]
+        show_uncited: bool,
+        /// Provide the response format, text, json or the path to a JSON schema file
+        #[clap(long)]
+        #[clap(default_value = CHAT_RESPONSE_FORMAT)]
+        format: String,
+        #[clap(flatten)]
+        generation: GenerationArgs,
+        #[clap(flatten)]
+        search: SearchArgs,
+        #[clap(flatten)]
+        rerank: RerankArgs,
     },
     /// Chat with the AI
     Generate {
         /// Prompt for AI
-        #[clap(short, long)]
+        #[clap(long)]
         prompt: String,
         /// Provide the model to use for query embedding
         #[clap(short = 'p', long)]
         #[clap(default_value = "ollama")]
         llm_provider: String,
         /// Provide the API endpoint to use
-        #[clap(short, long)]
+        #[clap(short = 'u', long)]
         #[clap(default_value = CHAT_API_URL)]
         api_url: String,
         /// Provide the API key to use
+        #[clap(short = 'k', long)]
+        #[clap(default_value = CHAT_API_KEY)]
+        api_key: String,
+        /// Provide the AI model to use for generation
+        #[clap(short, long)]
+        #[clap(default_value = AI_MODEL)]
+        ai_model: String,
+        /// Provide the system prompt, a built-in prompt name like software-engineer or a file path
+        #[clap(short, long)]
+        #[clap(default_value = GENERAL_PROMPT)]
+        system_prompt: String,
+        /// Provide a Handlebars template file to render the prompt
+        #[clap(long)]
+        template: Option<String>,
+        /// Provide the response format, text, json or the path to a JSON schema file
+        #[clap(long)]
+        #[clap(default_value = CHAT_RESPONSE_FORMAT)]
+        format: String,
+        #[clap(flatten)]
+        generation: GenerationArgs,
+    },
+    /// Manage the saved chat sessions
+    Sessions {
+        #[clap(subcommand)]
+        action: SessionAction,
+    },
+    /// List and show the system prompts
+    Prompts {
+        #[clap(subcommand)]
+        action: PromptAction,
+    },
+    /// List, inspect, drop and rename the tables of a database
+    Tables {
+        #[clap(subcommand)]
+        action: TableAction,
+    },
+    /// List, tag and restore the versions of a table
+    Versions {
+        #[clap(subcommand)]
+        action: VersionAction,
+    },
+}
+
+/// Generation options sent to the model, they replace the model defaults from the config file
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct GenerationArgs {
+    /// The sampling temperature between 0 and 2, lower is more deterministic
+    #[clap(long)]
+    pub temperature: Option<f32>,
+    /// The random seed, use the same seed and temperature for reproducible runs
+    #[clap(long)]
+    pub seed: Option<i32>,
+    /// The context window size in tokens
+    #[clap(long)]
+    pub num_ctx: Option<i32>,
+    /// The maximum number of tokens to generate, -1 for no limit
+    #[clap(long, allow_hyphen_values = true)]
+    pub num_predict: Option<i32>,
+    /// Stop generating at this sequence, can be repeated
+    #[clap(long)]
+    pub stop: Vec<String>,
+    /// JSON file with model options using the Ollama option names
+    #[clap(long)]
+    pub options_file: Option<String>,
+}
+
+impl GenerationArgs {
+    /// The options set on the command line
+    pub fn cli_options(&self) -> Options {
+        let mut builder = OptionsBuilder::new();
+        if let Some(temperature) = self.temperature {
+            builder = builder.temperature(temperature);
+        }
+        if let Some(seed) = self.seed {
+            builder = builder.seed(seed);
+        }
+        if let Some(num_ctx) = self.num_ctx {
+            builder = builder.num_ctx(num_ctx);
+        }
+        if let Some(num_predict) = self.num_predict {
+            builder = builder.num_predict(num_predict);
+        }
+        if !self.stop.is_empty() {
+            builder = builder.stop(self.stop.clone());
+        }
+        builder.build()
+    }
+}
+
+/// Parameters of the nearest vector search
+#[derive(clap::Args, Debug, Clone)]
+pub struct SearchArgs {
+    /// The number of rows returned by the vector search
+    #[clap(long)]
+    #[clap(default_value_t = QUERY_LIMIT)]
+    pub limit: usize,
+    /// The number of index partitions to search, more is slower with better recall
+    #[clap(long)]
+    #[clap(default_value_t = QUERY_NPROBES)]
+    pub nprobes: usize,
+    /// Re-score limit times refine factor rows with the full vectors
+    #[clap(long)]
+    #[clap(default_value_t = QUERY_REFINE_FACTOR)]
+    pub refine_factor: u32,
+    /// The distance metric, the table is refused if its index uses another metric
+    #[clap(long, value_parser = parse_distance_type)]
+    pub distance_type: Option<DistanceType>,
+    /// Drop the rows with a larger distance
+    #[clap(long)]
+    pub max_distance: Option<f32>,
+    /// Search the full-text index of the content instead of the vectors
+    #[clap(long)]
+    pub full_text: bool,
+    /// Filter the searched rows with key=value, the keys are path (a glob), language, model,
+    /// created_after, created_before (YYYY-MM-DD or RFC 3339) and symbol_kind, can be repeated
+    #[clap(long)]
+    pub filter: Vec<String>,
+    /// Skip this many rows, use it with the page size to read the whole table a page at a time
+    #[clap(long)]
+    #[clap(default_value_t = 0)]
+    pub offset: usize,
+    /// The number of rows returned by a whole table query, all rows if not provided
+    #[clap(long)]
+    pub page_size: Option<usize>,
+    /// Search this version of the table instead of the latest
+    #[clap(long = "version", value_name = "VERSION")]
+    pub table_version: Option<u64>,
+    /// Search the version of the table with this tag
+    #[clap(long, conflicts_with = "table_version")]
+    pub tag: Option<String>,
+}
+
+impl SearchArgs {
+    /// The query parameters set on the command line
+    pub fn query_params(&self) -> anyhow::Result<QueryParams> {
+        let filter = SearchFilter::parse(&self.filter)?;
+        Ok(QueryParams {
+            limit: self.limit,
+            nprobes: self.nprobes,
+            refine_factor: self.refine_factor,
+            distance_type: self.distance_type,
+            max_distance: self.max_distance,
+            full_text: self.full_text,
+            filter: filter.to_predicate(),
+            offset: self.offset,
+            page_size: self.page_size,
+            snapshot: Snapshot::from_args(self.table_version, self.tag.clone())?,
+        })
+    }
+}
+
+/// Parameters of the vector index
+#[derive(clap::Args, Debug, Clone)]
+pub struct IndexArgs {
+    /// The type of the vector index, flat leaves the table without an index
+    #[clap(long)]
+    #[clap(default_value = "ivf_hnsw_sq")]
+    pub index_type: IndexKind,
+    /// The number of IVF partitions, derived from the row count if not provided
+    #[clap(long)]
+    pub num_partitions: Option<u32>,
+    /// The number of PQ sub-vectors, derived from the embedding dimension if not provided
+    #[clap(long)]
+    pub num_sub_vectors: Option<u32>,
+    /// Tables with fewer rows are not indexed since a scan is fast and exact
+    #[clap(long)]
+    #[clap(default_value_t = INDEX_MIN_ROWS)]
+    pub min_index_rows: usize,
+}
+
+impl IndexArgs {
+    /// The index parameters set on the command line
+    pub fn index_params(&self) -> IndexParams {
+        IndexParams {
+            kind: self.index_type,
+            num_partitions: self.num_partitions,
+            num_sub_vectors: self.num_sub_vectors,
+            min_rows: self.min_index_rows,
+        }
+    }
+}
+
+/// Retention of the old table versions
+#[derive(clap::Args, Debug, Clone)]
+pub struct OptimizeArgs {
+    /// Versions older than this many days are pruned
+    #[clap(long)]
+    #[clap(default_value_t = OPTIMIZE_RETENTION_DAYS)]
+    pub retention_days: i64,
+    /// Also delete the files of versions newer than 7 days, only safe when nothing else writes the table
+    #[clap(long)]
+    pub delete_unverified: bool,
+}
+
+impl OptimizeArgs {
+    /// The optimize parameters set on the command line
+    pub fn optimize_params(&self) -> OptimizeParams {
+        OptimizeParams {
+            retention_days: self.retention_days,
+            delete_unverified: self.delete_unverified,
+        }
+    }
+}
+
+fn parse_distance_type(value: &str) -> Result<DistanceType, String> {
+    DistanceType::try_from(value).map_err(|e| e.to_string())
+}
+
+/// Rerank stage between the vector search and the chat
+#[derive(clap::Args, Debug, Clone)]
+pub struct RerankArgs {
+    /// Rerank the retrieved chunks with a rerank endpoint or the chat model
+    #[clap(long)]
+    pub rerank: Option<RerankMethod>,
+    /// The rerank model, the AI model is used for llm reranking if not provided
+    #[clap(long)]
+    pub rerank_model: Option<String>,
+    /// The API endpoint of the rerank model, the API URL is used if not provided
+    #[clap(long)]
+    pub rerank_url: Option<String>,
+    /// The number of chunks from the vector search to rerank
+    #[clap(long)]
+    #[clap(default_value_t = RERANK_CANDIDATES)]
+    pub rerank_candidates: usize,
+    /// The number of chunks kept after reranking
+    #[clap(long)]
+    #[clap(default_value_t = RERANK_TOP_K)]
+    pub rerank_top_k: usize,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PromptAction {
+    /// List the built-in prompts and the prompts in the config directory
+    List,
+    /// Show the content of a prompt
+    Show {
+        /// The name of the prompt
         #[clap(short, long)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SessionAction {
+    /// List the saved chat sessions
+    List,
+    /// Resume a saved chat session
+    Resume {
+        /// The id of the session to resume
+        #[clap(short, long)]
+        id: String,
+        /// Provide the provider to use for the chat
+        #[clap(short = 'p', long)]
+        #[clap(default_value = "ollama")]
+        llm_provider: String,
+        /// Provide the API endpoint to use
+        #[clap(short = 'u', long)]
+        #[clap(default_value = CHAT_API_URL)]
+        api_url: String,
+        /// Provide the API key to use
+        #[clap(short = 'k', long)]
         #[clap(default_value = CHAT_API_KEY)]
         api_key: String,
         /// Provide the AI model to use for generation
         #[clap(short, long)]
         #[clap(default_value = AI_MODEL)]
         ai_model: String,
+        /// Provide the system prompt, a built-in prompt name like software-engineer or a file path
+        #[clap(short, long)]
+        #[clap(default_value = SYSTEM_PROMPT)]
+        system_prompt: String,
+        /// Provide the response format, text, json or the path to a JSON schema file
+        #[clap(long)]
+        #[clap(default_value = CHAT_RESPONSE_FORMAT)]
+        format: String,
+        #[clap(flatten)]
+        generation: GenerationArgs,
+    },
+    /// Delete a saved chat session
+    Delete {
+        /// The id of the session to delete
+        #[clap(short, long)]
+        id: String,
+    },
+    /// Export a chat session to Markdown
+    Export {
+        /// The id of the session to export
+        #[clap(short, long)]
+        id: String,
+        /// The file to write the Markdown to, printed to stdout if not provided
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VersionAction {
+    /// List the versions of a table with their tags
+    List {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to use
+        #[clap(short, long)]
+        table: String,
+    },
+    /// Tag a version of a table, a tag given again is moved
+    Tag {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to use
+        #[clap(short, long)]
+        table: String,
+        /// The name of the tag
+        #[clap(short, long)]
+        name: String,
+        /// The version to tag, the latest version if not provided
+        #[clap(long = "version", value_name = "VERSION")]
+        table_version: Option<u64>,
+    },
+    /// Remove a tag of a table so its versions can be pruned
+    Untag {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to use
+        #[clap(short, long)]
+        table: String,
+        /// The name of the tag
+        #[clap(short, long)]
+        name: String,
+    },
+    /// Restore a version of a table as its latest version
+    Restore {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to use
+        #[clap(short, long)]
+        table: String,
+        /// The version to restore
+        #[clap(
+            long = "version",
+            value_name = "VERSION",
+            required_unless_present = "tag"
+        )]
+        table_version: Option<u64>,
+        /// The tag of the version to restore
+        #[clap(long, conflicts_with = "table_version")]
+        tag: Option<String>,
+        /// Restore without asking for confirmation
+        #[clap(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TableAction {
+    /// List the tables of a database
+    List {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+    },
+    /// Show the schema, schema metadata and index status of a table
+    Describe {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to describe
+        #[clap(short, long)]
+        table: String,
+    },
+    /// Show the row count, file count, models, embedding dimension, load times and size of a table
+    Stats {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to summarize
+        #[clap(short, long)]
+        table: String,
+    },
+    /// Drop a table and its data
+    Drop {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to drop
+        #[clap(short, long)]
+        table: String,
+        /// Drop without asking for confirmation
+        #[clap(short, long)]
+        yes: bool,
+    },
+    /// Remove the placeholder rows that older versions inserted into new tables
+    Cleanup {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to clean up
+        #[clap(short, long)]
+        table: String,
+    },
+    /// List the sources loaded into the tables
+    Sources {
+        /// Only list the sources of this database
+        #[clap(short, long)]
+        database: Option<String>,
+    },
+    /// Rename a table
+    Rename {
+        /// Provide the database to use
+        #[clap(short, long)]
+        database: String,
+        /// Provide the table to rename
+        #[clap(short, long)]
+        table: String,
+        /// The new name of the table
+        #[clap(short, long)]
+        new_name: String,
+        /// Rename without asking for confirmation
+        #[clap(short, long)]
+        yes: bool,
     },
 }
 
@@ -211,6 +739,15 @@ impl Commands {
             input
         }
     }
+
+    /// Ask the user to confirm, true if the answer is y or yes or the confirmation is skipped
+    pub fn confirm_from_cli(skip: bool, prompt_message: &str) -> bool {
+        if skip {
+            return true;
+        }
+        let answer = fetch_value(&format!("{} [y/N]: ", prompt_message));
+        matches!(answer.to_lowercase().as_str(), "y" | "yes")
+    }
 }
 
 impl LogLevel {
@@ -311,6 +848,16 @@ pub fn dbg_cmd() {
             embed_model,
             api_url,
             api_key,
+            distance_type,
+            database,
+            table,
+            append,
+            source,
+            index,
+            tag,
+            tag_git_commit,
+            no_optimize,
+            optimize,
         } => {
             println!("Load command");
             println!("Path: {:?}", path);
@@ -319,9 +866,44 @@ pub fn dbg_cmd() {
             println!("Embed Model: {:?}", embed_model);
             println!("API URL: {:?}", api_url);
             println!("API Key: {:?}", api_key);
+            println!("Distance Type: {:?}", distance_type);
+            println!("Database: {:?}", database);
+            println!("Table: {:?}", table);
+            println!("Append: {:?}", append);
+            println!("Source: {:?}", source);
+            println!("Index: {:?}", index);
+            println!("Tag: {:?}", tag);
+            println!("Tag Git Commit: {:?}", tag_git_commit);
+            println!("No Optimize: {:?}", no_optimize);
+            println!("Optimize: {:?}", optimize);
+        }
+        Commands::Optimize {
+            database,
+            table,
+            optimize,
+        } => {
+            println!("Optimize command");
+            println!("Database: {:?}", database);
+            println!("Table: {:?}", table);
+            println!("Optimize: {:?}", optimize);
+        }
+        Commands::Reindex {
+            database,
+            table,
+            distance_type,
+            index,
+        } => {
+            println!("Reindex command");
+            println!("Database: {:?}", database);
+            println!("Table: {:?}", table);
+            println!("Distance Type: {:?}", distance_type);
+            println!("Index: {:?}", index);
         }
         Commands::LanceQuery {
             input,
+            fuse,
+            batch,
+            output,
             llm_provider,
             api_url,
             api_key,
@@ -330,9 +912,13 @@ pub fn dbg_cmd() {
             database,
             whole_query,
             file_context,
+            search,
         } => {
             println!("Lance Query command");
             println!("Query: {:?}", input);
+            println!("Fuse: {:?}", fuse);
+            println!("Batch: {:?}", batch);
+            println!("Output: {:?}", output);
             println!("LLM Provider: {:?}", llm_provider);
             println!("API URL: {:?}", api_url);
             println!("API Key: {:?}", api_key);
@@ -341,6 +927,7 @@ pub fn dbg_cmd() {
             println!("Database: {:?}", database);
             println!("Whole Query: {:?}", whole_query);
             println!("File Context: {:?}", file_context);
+            println!("Search: {:?}", search);
         }
         Commands::RagQuery {
             input,
@@ -354,6 +941,17 @@ pub fn dbg_cmd() {
             whole_query,
             file_context: file_query,
             system_prompt,
+            template,
+            tools,
+            max_tool_rounds,
+            agentic,
+            max_steps,
+            verbose,
+            show_uncited,
+            format,
+            generation,
+            search,
+            rerank,
         } => {
             println!("Lance Query command");
             let cli_input = Commands::fetch_prompt_from_cli(input.clone(), "Enter query: ");
@@ -368,6 +966,17 @@ pub fn dbg_cmd() {
             println!("Whole Query: {:?}", whole_query);
             println!("File Query: {:?}", file_query);
             println!("System Prompt: {:?}", system_prompt);
+            println!("Template: {:?}", template);
+            println!("Tools: {:?}", tools);
+            println!("Max Tool Rounds: {:?}", max_tool_rounds);
+            println!("Agentic: {:?}", agentic);
+            println!("Max Steps: {:?}", max_steps);
+            println!("Verbose: {:?}", verbose);
+            println!("Show Uncited: {:?}", show_uncited);
+            println!("Format: {:?}", format);
+            println!("Generation: {:?}", generation);
+            println!("Search: {:?}", search);
+            println!("Rerank: {:?}", rerank);
         }
         Commands::Generate {
             prompt,
@@ -375,6 +984,10 @@ pub fn dbg_cmd() {
             api_url,
             api_key,
             ai_model,
+            system_prompt,
+            template,
+            format,
+            generation,
         } => {
             println!("Chat command");
             println!("Prompt: {:?}", prompt);
@@ -382,6 +995,26 @@ pub fn dbg_cmd() {
             println!("API URL: {:?}", api_url);
             println!("API Key: {:?}", api_key);
             println!("AI Model: {:?}", ai_model);
+            println!("System Prompt: {:?}", system_prompt);
+            println!("Template: {:?}", template);
+            println!("Format: {:?}", format);
+            println!("Generation: {:?}", generation);
+        }
+        Commands::Sessions { action } => {
+            println!("Sessions command");
+            println!("Action: {:?}", action);
+        }
+        Commands::Prompts { action } => {
+            println!("Prompts command");
+            println!("Action: {:?}", action);
+        }
+        Commands::Tables { action } => {
+            println!("Tables command");
+            println!("Action: {:?}", action);
+        }
+        Commands::Versions { action } => {
+            println!("Versions command");
+            println!("Action: {:?}", action);
         }
     }
 }
@@ -0,0 +1,95 @@
+use crate::app::constants::{APP_NAME, CONFIG_FILE};
+use crate::chat::model_options::Options;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// AppConfig is the user configuration read from `<config dir>/config.json`
+/// ```json
+/// {
+///   "models": { "qwen2:7b": { "temperature": 0.2, "seed": 42, "num_ctx": 16384 } },
+///   "storage": { "s3://team-index": { "aws_region": "eu-west-1", "aws_secret_access_key": "env:TEAM_SECRET" } }
+/// }
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Default generation options per model name
+    pub models: HashMap<String, Options>,
+    /// Lance storage options per database URI prefix, like the region, endpoint and credentials
+    /// of an object store, a value `env:NAME` is read from the environment variable NAME
+    pub storage: HashMap<String, HashMap<String, String>>,
+}
+
+impl AppConfig {
+    /// Load the config file, the default config is used if there is no config file
+    pub fn load() -> Result<AppConfig> {
+        let path = app_config_dir()?.join(CONFIG_FILE);
+        if !path.is_file() {
+            return Ok(AppConfig::default());
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))
+    }
+
+    /// Get the default options for the model
+    pub fn model_options(&self, model: &str) -> Option<&Options> {
+        self.models.get(model)
+    }
+
+    /// Get the storage options of the database URI from the longest matching prefix
+    /// # Arguments
+    /// * `uri` - The database URI like `s3://bucket/path`
+    /// # Returns
+    /// * `Result<HashMap<String, String>>` - The options, an `env:` value without its variable is an error
+    pub fn storage_options(&self, uri: &str) -> Result<HashMap<String, String>> {
+        let Some((_, options)) = self
+            .storage
+            .iter()
+            .filter(|(prefix, _)| uri.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+        else {
+            return Ok(HashMap::new());
+        };
+
+        options
+            .iter()
+            .map(|(key, value)| {
+                let value = match value.strip_prefix("env:") {
+                    Some(name) => std::env::var(name).with_context(|| {
+                        format!(
+                            "Environment variable {} of storage option {} is not set",
+                            name, key
+                        )
+                    })?,
+                    None => value.clone(),
+                };
+                Ok((key.clone(), value))
+            })
+            .collect()
+    }
+}
+
+/// Get the directory for application data like chat sessions
+/// `~/.local/share/vector-embed-rust` on linux
+/// # Returns
+/// * `Result<PathBuf>` - The data directory
+pub fn app_data_dir() -> Result<PathBuf> {
+    dirs::data_local_dir()
+        .map(|dir| dir.join(APP_NAME))
+        .ok_or_else(|| anyhow!("Failed to find the local data directory"))
+}
+
+/// Get the directory for user configuration like custom prompts
+/// `~/.config/vector-embed-rust` on linux
+/// # Returns
+/// * `Result<PathBuf>` - The configuration directory
+pub fn app_config_dir() -> Result<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join(APP_NAME))
+        .ok_or_else(|| anyhow!("Failed to find the configuration directory"))
+}
@@ -6,12 +6,36 @@ pub const EMBEDDING_MODEL: &str = "nomic-embed-text";
 // pub const VECTOR_DB_DIM_STR: &str = "768";
 pub const VECTOR_DB_DIM_SIZE: i32 = 768;
 pub const VERSION: &str = "1.0.0";
+pub const APP_NAME: &str = "vector-embed-rust";
 // pub const QUERY_LIMIT: i64 = 1;
-pub const LANCEDB_DISTANCE_FN: lancedb::DistanceType = lancedb::DistanceType::L2;
+pub const QUERY_LIMIT: usize = 30; // rows returned by the nearest vector query
+pub const QUERY_NPROBES: usize = 40; // index partitions searched, the lancedb default is 20
+pub const QUERY_REFINE_FACTOR: u32 = 10; // rows re-scored with the full vectors per result
+pub const QUERY_CONCURRENCY: usize = 4; // query inputs searched at the same time
+pub const QUERY_BATCH_SIZE: usize = 32; // query inputs of a batch file embedded in one request
+pub const RRF_K: f32 = 60.0; // rank constant of the reciprocal rank fusion
+pub const LANCEDB_DISTANCE_FN: lancedb::DistanceType = lancedb::DistanceType::Cosine;
+pub const DISTANCE_TYPE_KEY: &str = "distance_type"; // schema metadata key of the index distance type
+pub const VECTOR_INDEX_KEY: &str = "vector_index"; // schema metadata key of the vector index kind
+pub const EMBED_MODEL_KEY: &str = "embed_model"; // schema metadata key of the model the vectors are embedded with
+pub const INDEX_MIN_ROWS: usize = 5000; // tables with fewer rows are scanned instead of indexed
+pub const INDEX_HNSW_PARTITION_ROWS: usize = 1_048_576; // rows per IVF partition of the HNSW indices
 pub const CHAT_API_URL: &str = "http://10.0.0.213:11434";
 pub const CHAT_API_KEY: &str = "api_key";
-pub const CHAT_RESPONSE_FORMAT: &str = "json";
-pub const SYSTEM_PROMPT_PATH: &str = "src/resources/rag_prompt.txt";
+pub const CHAT_RESPONSE_FORMAT: &str = "text"; // text, json or path to a JSON schema file
+pub const CHAT_FORMAT_RETRIES: usize = 2; // retries when the response does not match the format
+pub const CHAT_MAX_TOOL_ROUNDS: usize = 5; // tool call rounds before the model has to answer
+pub const AGENTIC_MAX_STEPS: usize = 3; // planning steps of the agentic retrieval
+pub const CHAT_CONTEXT_WINDOW: i32 = 8192; // num_ctx sent to the model and used as the history token budget
+pub const CHAT_RESPONSE_TOKENS: usize = 1024; // tokens reserved for the model response
+pub const CHAT_SUMMARY_TOKENS: usize = 300; // tokens reserved for the summary of trimmed turns
+pub const RERANK_CANDIDATES: usize = 20; // chunks from the vector search that are reranked
+pub const RERANK_TOP_K: usize = 8; // chunks kept after reranking
+pub const RERANK_CONCURRENCY: usize = 4; // chunks scored at the same time by the chat model
+pub const RERANK_MODEL: &str = "bge-reranker-v2-m3";
+pub const RAG_CONTEXT_SHARE: usize = 60; // percent of the context window after the response used for retrieved chunks
+pub const SYSTEM_PROMPT: &str = "rag"; // built-in prompt name or path to a prompt file
+pub const GENERAL_PROMPT: &str = "general";
 pub const AI_MODEL: &str = "qwen2:7b"; //"mistral:latest";
 
 pub const OLLAMA_CHAT_API: &str = "api/chat";
@@ -19,5 +43,14 @@ pub const OLLAMA_EMBED_API: &str = "api/embed";
 pub const OPEN_AI_URL: &str = "https://api.openai.com";
 pub const OPEN_AI_CHAT_API: &str = "v1/chat/completions";
 pub const OPEN_AI_EMBED_API: &str = "v1/embeddings";
+pub const RERANK_API: &str = "v1/rerank";
 
 // pub const DEFAULT_CHUNK_SIZE: usize = 2048;
+pub const SESSIONS_DIR: &str = "sessions";
+/// Chats started in the same second get the session ids `<time>-2` up to `<time>-100`
+pub const MAX_SESSION_ID_ATTEMPTS: usize = 100;
+pub const PROMPTS_DIR: &str = "prompts";
+pub const CONFIG_FILE: &str = "config.json";
+pub const OBJECT_STORE_SCHEMES: [&str; 6] = ["file", "memory", "s3", "s3+ddb", "gs", "az"]; // database URI schemes Lance can open
+pub const SOURCES_FILE: &str = "sources.json"; // registry of the sources loaded into tables
+pub const OPTIMIZE_RETENTION_DAYS: i64 = 7; // versions older than this are pruned by optimize
@@ -3,4 +3,5 @@ pub mod cli;
 #[allow(non_snake_case)]
 #[allow(dead_code)]
 pub mod commands;
+pub mod config;
 pub mod constants;
@@ -0,0 +1,302 @@
+use crate::chat::chat_config::ChatRequest;
+use crate::chat::prompt_template::Prompt;
+use crate::chat::response_format::ResponseFormat;
+use crate::lancevectordb::query::{self, QueryParams, QueryRecord};
+use crate::lancevectordb::HttpsClient;
+use anyhow::{Context, Result};
+use lancedb::Connection;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::json;
+
+const PLANNER_SYSTEM_PROMPT: &str = "You plan searches in a code repository to answer a question. \
+You get the question and the code chunks found so far. \
+If the chunks are enough to answer the question set sufficient to true. \
+Otherwise split what is missing into short search queries for the code, \
+for example function names, types or what the code does, and do not repeat earlier queries. \
+Answer only with JSON.";
+
+/// Number of characters of each chunk shown to the planner
+const PLANNER_PREVIEW_CHARS: usize = 200;
+/// Number of chunks shown to the planner, the nearest chunks are shown
+const PLANNER_MAX_PREVIEWS: usize = 30;
+
+/// Plan is the answer of the model for one retrieval step
+#[derive(Deserialize, Debug)]
+struct Plan {
+    sufficient: bool,
+    #[serde(default)]
+    queries: Vec<String>,
+    #[serde(default)]
+    reason: String,
+}
+
+/// AgenticRetriever lets the model split the question into sub-queries
+/// and searches until the model has enough context or the step limit is reached
+pub(crate) struct AgenticRetriever {
+    db: Connection,
+    table: String,
+    provider: String,
+    api_url: String,
+    api_key: String,
+    embed_model: String,
+    ai_model: String,
+    max_steps: usize,
+    verbose: bool,
+    query_params: QueryParams,
+}
+
+impl AgenticRetriever {
+    pub fn new(
+        db: Connection,
+        table: &str,
+        provider: &str,
+        api_url: &str,
+        api_key: &str,
+        embed_model: &str,
+        ai_model: &str,
+    ) -> AgenticRetriever {
+        AgenticRetriever {
+            db,
+            table: table.to_string(),
+            provider: provider.to_string(),
+            api_url: api_url.to_string(),
+            api_key: api_key.to_string(),
+            embed_model: embed_model.to_string(),
+            ai_model: ai_model.to_string(),
+            max_steps: 1,
+            verbose: false,
+            query_params: QueryParams::default(),
+        }
+    }
+
+    /// Set the maximum number of planning steps
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    /// Print the sub-queries and results of every step
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Set the parameters of the vector search of every query
+    pub fn with_query_params(mut self, query_params: QueryParams) -> Self {
+        self.query_params = query_params;
+        self
+    }
+
+    /// Retrieve the chunks for the question
+    /// The question is searched first, then every step the model gets the chunks found so far
+    /// and answers with new sub-queries until it reports sufficient context.
+    /// # Arguments
+    /// * `question` - The user question
+    /// * `client` - The HTTP client to use for requests
+    /// # Returns
+    /// * `Result<Vec<QueryRecord>>` - The deduplicated chunks, nearest first
+    pub async fn retrieve(&self, question: &str, client: &HttpsClient) -> Result<Vec<QueryRecord>> {
+        let mut records: Vec<QueryRecord> = Vec::new();
+        let mut searched: Vec<String> = vec![question.to_string()];
+
+        let found = self.search(question, client).await?;
+        let added = merge_records(&mut records, found);
+        self.report(&format!("Step 0: searched the question, {} chunks", added));
+
+        for step in 1..=self.max_steps {
+            // a plan that is not valid JSON ends the retrieval with the chunks found so far
+            let plan = match self.plan(question, &searched, &records, client).await {
+                Ok(plan) => plan,
+                Err(e) => {
+                    warn!(
+                        "Failed to plan retrieval step {}, using the {} chunks found: {:#}",
+                        step,
+                        records.len(),
+                        e
+                    );
+                    break;
+                }
+            };
+            debug!("Retrieval plan: {:?}", plan);
+
+            if plan.sufficient {
+                self.report(&format!(
+                    "Step {}: sufficient context, {}",
+                    step, plan.reason
+                ));
+                break;
+            }
+
+            let queries: Vec<String> = plan
+                .queries
+                .into_iter()
+                .map(|q| q.trim().to_string())
+                .filter(|q| !q.is_empty() && !searched.contains(q))
+                .collect();
+            if queries.is_empty() {
+                self.report(&format!("Step {}: no new queries, stopping", step));
+                break;
+            }
+
+            let mut step_added = 0;
+            for sub_query in &queries {
+                let found = self.search(sub_query, client).await?;
+                let added = merge_records(&mut records, found);
+                self.report(&format!(
+                    "Step {}: \"{}\" added {} chunks",
+                    step, sub_query, added
+                ));
+                step_added += added;
+            }
+            searched.extend(queries);
+
+            if step_added == 0 {
+                self.report(&format!("Step {}: no new chunks, stopping", step));
+                break;
+            }
+        }
+
+        records.sort_by(|a, b| {
+            let a = a.distance.unwrap_or(f32::MAX);
+            let b = b.distance.unwrap_or(f32::MAX);
+            a.total_cmp(&b)
+        });
+        info!(
+            "Agentic retrieval found {} chunks with {} queries",
+            records.len(),
+            searched.len()
+        );
+
+        Ok(records)
+    }
+
+    async fn search(&self, search: &str, client: &HttpsClient) -> Result<Vec<QueryRecord>> {
+        let mut db = self.db.clone();
+        query::run_query(
+            &mut db,
+            &self.provider,
+            &self.api_url,
+            &self.api_key,
+            &self.embed_model,
+            &vec![search.to_string()],
+            &self.table,
+            client,
+            false,
+            false,
+            &self.query_params,
+        )
+        .await
+        .with_context(|| format!("Failed to search for {}", search))
+    }
+
+    async fn plan(
+        &self,
+        question: &str,
+        searched: &[String],
+        records: &[QueryRecord],
+        client: &HttpsClient,
+    ) -> Result<Plan> {
+        let mut nearest: Vec<&QueryRecord> = records.iter().collect();
+        nearest.sort_by(|a, b| {
+            let a = a.distance.unwrap_or(f32::MAX);
+            let b = b.distance.unwrap_or(f32::MAX);
+            a.total_cmp(&b)
+        });
+        let mut chunks = nearest
+            .iter()
+            .take(PLANNER_MAX_PREVIEWS)
+            .map(|r| {
+                let preview: String = r.content.chars().take(PLANNER_PREVIEW_CHARS).collect();
+                format!(
+                    "- {} chunk {}: {}",
+                    r.metadata,
+                    r.chunk_number.map_or("?".to_string(), |c| c.to_string()),
+                    preview.replace('\n', " ")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if records.len() > PLANNER_MAX_PREVIEWS {
+            chunks.push_str(&format!(
+                "\n- and {} more chunks",
+                records.len() - PLANNER_MAX_PREVIEWS
+            ));
+        }
+
+        let prompt = Prompt {
+            system_message: PLANNER_SYSTEM_PROMPT.to_string(),
+            context: None,
+            content: Vec::new(),
+            prompt: format!(
+                "Question: {}\n\nQueries searched: {}\n\nChunks found:\n{}",
+                question,
+                searched.join("; "),
+                chunks
+            ),
+        };
+
+        let format = ResponseFormat::JsonSchema(json!({
+            "type": "object",
+            "properties": {
+                "sufficient": { "type": "boolean" },
+                "queries": { "type": "array", "items": { "type": "string" } },
+                "reason": { "type": "string" }
+            },
+            "required": ["sufficient", "queries"]
+        }));
+
+        let chat_request = ChatRequest::new(
+            &self.provider,
+            &self.ai_model,
+            self.api_url.clone(),
+            self.api_key.clone(),
+            false,
+            Some(format.clone()),
+            None,
+            prompt,
+        );
+
+        let response = super::send_chat(chat_request, &format, None, client).await?;
+        let content = response
+            .get_message()
+            .map(|m| m.get_content().as_str())
+            .unwrap_or_default();
+
+        serde_json::from_str(content).context("Failed to parse retrieval plan")
+    }
+
+    fn report(&self, message: &str) {
+        if self.verbose {
+            println!("{}", message);
+        } else {
+            debug!("{}", message);
+        }
+    }
+}
+
+/// Merge the found records, a chunk found again keeps the smallest distance
+/// Returns the number of new chunks
+fn merge_records(records: &mut Vec<QueryRecord>, found: Vec<QueryRecord>) -> usize {
+    let mut added = 0;
+    for record in found {
+        let existing = records.iter_mut().find(|r| {
+            r.metadata == record.metadata
+                && r.chunk_number == record.chunk_number
+                && r.content == record.content
+        });
+        match existing {
+            Some(existing) => {
+                if record.distance.unwrap_or(f32::MAX) < existing.distance.unwrap_or(f32::MAX) {
+                    existing.distance = record.distance;
+                    existing.similarity = record.similarity;
+                }
+            }
+            None => {
+                records.push(record);
+                added += 1;
+            }
+        }
+    }
+    added
+}
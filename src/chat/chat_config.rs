@@ -1,5 +1,6 @@
 use crate::app::constants::{self, OPEN_AI_CHAT_API, OPEN_AI_URL};
 use crate::chat::model_options::Options;
+use crate::chat::response_format::ResponseFormat;
 use crate::lancevectordb::HttpsClient;
 use anyhow::Result;
 use anyhow::{anyhow, Context};
@@ -7,7 +8,7 @@ use bytes::Bytes;
 use http_body_util::Full;
 use log::debug;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::fmt;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -15,7 +16,7 @@ use tokio::sync::RwLock;
 use crate::chat::prompt_template::Prompt;
 
 /// ChatRole is an enum that represents the role of the chat message
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 
 pub enum ChatRole {
     #[serde(rename = "user")]
@@ -29,10 +30,73 @@ pub enum ChatRole {
 }
 
 /// ChatMessage is a struct that represents a chat message
+/// `tool_calls` is set on assistant messages that call tools,
+/// `tool_call_id` on tool messages answering an OpenAI tool call
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ChatMessage {
     role: ChatRole,
+    #[serde(default, deserialize_with = "null_as_empty")]
     content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+/// OpenAI sends `"content": null` on messages with tool calls
+fn null_as_empty<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// ToolCall is a function call requested by the model
+/// Ollama sends the arguments as a JSON object and OpenAI as a JSON string
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ToolCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    call_type: Option<String>,
+    function: ToolFunction,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ToolFunction {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+impl ToolCall {
+    pub fn get_id(&self) -> Option<&String> {
+        self.id.as_ref()
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.function.name
+    }
+
+    /// The arguments of the call as a JSON object
+    pub fn get_arguments(&self) -> Result<Value> {
+        match &self.function.arguments {
+            Value::String(arguments) => serde_json::from_str(arguments)
+                .with_context(|| format!("Failed to parse tool arguments: {}", arguments)),
+            Value::Null => Ok(Value::Object(Map::new())),
+            arguments => Ok(arguments.clone()),
+        }
+    }
+
+    /// OpenAI expects the arguments as a JSON string when the call is sent back
+    fn with_string_arguments(&self) -> ToolCall {
+        let mut call = self.clone();
+        if !call.function.arguments.is_string() {
+            call.function.arguments = Value::String(call.function.arguments.to_string());
+        }
+        call.call_type.get_or_insert_with(|| "function".to_string());
+        call
+    }
 }
 
 impl fmt::Display for ChatMessage {
@@ -43,13 +107,48 @@ impl fmt::Display for ChatMessage {
 
 impl ChatMessage {
     pub fn new(role: ChatRole, content: String) -> ChatMessage {
-        ChatMessage { role, content }
+        ChatMessage {
+            role,
+            content,
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+
+    /// Create the tool message with the result of a tool call
+    pub fn tool_result(call: &ToolCall, content: String) -> ChatMessage {
+        ChatMessage {
+            role: ChatRole::Tool,
+            content,
+            tool_calls: None,
+            tool_call_id: call.get_id().cloned(),
+        }
+    }
+
+    /// The tool calls requested by the model, empty if the model answered
+    pub fn get_tool_calls(&self) -> &[ToolCall] {
+        self.tool_calls.as_deref().unwrap_or_default()
+    }
+
+    /// Convert the message to the shape the provider expects in a request
+    fn for_provider(&self, provider: &LLMProvider) -> ChatMessage {
+        match (provider, &self.tool_calls) {
+            (LLMProvider::OpenAI, Some(calls)) => ChatMessage {
+                tool_calls: Some(calls.iter().map(|c| c.with_string_arguments()).collect()),
+                ..self.clone()
+            },
+            _ => self.clone(),
+        }
     }
 
     pub fn get_content(&self) -> &String {
         &self.content
     }
 
+    pub fn get_role(&self) -> &ChatRole {
+        &self.role
+    }
+
     #[allow(dead_code)]
     pub fn pretty_print_chat(&self) {
         let content = self.get_content();
@@ -92,8 +191,11 @@ pub(crate) struct ChatRequest {
     pub api_key: String,
     pub messages: Vec<ChatMessage>,
     pub stream: bool,
-    pub format: String,
+    #[serde(skip)]
+    pub format: Option<ResponseFormat>,
     pub options: Option<Options>,
+    /// Tool definitions the model can call
+    pub tools: Option<Vec<Value>>,
 }
 
 /// ChatBody is a struct that represents the body of a chat request
@@ -102,9 +204,19 @@ struct ChatBody {
     model: String,
     pub messages: Vec<ChatMessage>,
     stream: bool,
-    format: String,
+    /// Ollama `format`, `"json"` or a JSON schema
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<Value>,
+    /// OpenAI `response_format`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     options: Option<Options>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Value>>,
+    /// OpenAI takes the generation options as top level fields
+    #[serde(flatten)]
+    openai_options: Map<String, Value>,
 }
 
 impl ChatRequest {
@@ -114,7 +226,7 @@ impl ChatRequest {
         api_url: String,
         api_key: String,
         stream: bool,
-        format: String,
+        format: Option<ResponseFormat>,
         options: Option<Options>,
         prompt: Prompt,
     ) -> ChatRequest {
@@ -122,11 +234,16 @@ impl ChatRequest {
         let system_message = ChatMessage::new(ChatRole::System, prompt.system_message);
         messages.push(system_message);
 
-        for content in prompt.content.clone().into_iter().flatten() {
-            let user_content = ChatMessage::new(ChatRole::User, content.get_content().to_string());
-            messages.push(user_content);
+        // Retrieved context goes into its own system message so it is never mistaken for user text
+        if let Some(context) = prompt.context.filter(|c| !c.is_empty()) {
+            let context_message =
+                ChatMessage::new(ChatRole::System, format!("Context:\n{}", context));
+            messages.push(context_message);
         }
 
+        // History keeps the role each message was recorded with
+        messages.extend(prompt.content);
+
         let user_prompt = ChatMessage::new(ChatRole::User, prompt.prompt);
         messages.push(user_prompt);
 
@@ -142,16 +259,46 @@ impl ChatRequest {
             stream,
             format,
             options,
+            tools: None,
         }
     }
 
+    /// Set the tool definitions the model can call
+    pub(crate) fn with_tools(mut self, tools: Vec<Value>) -> ChatRequest {
+        self.tools = Some(tools);
+        self
+    }
+
     pub(crate) fn create_chat_body(&self) -> Result<String> {
+        let (format, response_format) = self
+            .format
+            .as_ref()
+            .map_or((None, None), |f| f.request_field(&self.provider));
+
+        let (options, openai_options) = match self.provider {
+            LLMProvider::Ollama => (self.options.clone(), Map::new()),
+            LLMProvider::OpenAI => (
+                None,
+                self.options
+                    .as_ref()
+                    .map(|o| o.openai_fields())
+                    .unwrap_or_default(),
+            ),
+        };
+
         let chat_body = ChatBody {
             model: self.model.to_string(),
-            messages: self.messages.clone(),
+            messages: self
+                .messages
+                .iter()
+                .map(|m| m.for_provider(&self.provider))
+                .collect(),
             stream: self.stream,
-            format: self.format.to_string(),
-            options: self.options.clone(),
+            format,
+            response_format,
+            options,
+            tools: self.tools.clone(),
+            openai_options,
         };
 
         let body = serde_json::to_string(&chat_body).context("Failed to serialize ChatBody")?;
@@ -220,12 +367,66 @@ pub async fn ai_chat(
         .to_bytes();
     // debug!("Response body: {:?}", body.len());
 
-    let response_body: ChatResponse =
-        serde_json::from_slice(&body).context("Failed to parse response")?;
+    let response_body: ChatResponse = match chat_request.provider {
+        LLMProvider::Ollama => serde_json::from_slice(&body).context("Failed to parse response")?,
+        LLMProvider::OpenAI => serde_json::from_slice::<OpenAIResponse>(&body)
+            .context("Failed to parse response")?
+            .try_into()?,
+    };
 
     Ok(response_body)
 }
 
+/// OpenAIResponse is the response of the OpenAI chat completions API
+#[derive(Deserialize, Debug)]
+struct OpenAIResponse {
+    model: String,
+    created: i64,
+    choices: Vec<OpenAIChoice>,
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIChoice {
+    message: ChatMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIUsage {
+    prompt_tokens: i32,
+    completion_tokens: i32,
+}
+
+impl TryFrom<OpenAIResponse> for ChatResponse {
+    type Error = anyhow::Error;
+
+    fn try_from(response: OpenAIResponse) -> Result<ChatResponse> {
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("OpenAI response has no choices"))?;
+
+        Ok(ChatResponse {
+            model: response.model,
+            created_at: chrono::DateTime::from_timestamp(response.created, 0)
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default(),
+            message: choice.message,
+            done_reason: choice.finish_reason,
+            done: true,
+            context: None,
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_count: response.usage.as_ref().map(|u| u.prompt_tokens),
+            prompt_eval_duration: None,
+            eval_count: response.usage.as_ref().map(|u| u.completion_tokens),
+            eval_duration: None,
+        })
+    }
+}
+
 /// ChatResponse is a struct that represents a chat response
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ChatResponse {
@@ -247,4 +448,32 @@ impl ChatResponse {
     pub fn get_message(&self) -> Option<&ChatMessage> {
         Some(&self.message)
     }
+
+    pub fn get_model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn get_total_duration(&self) -> Option<i64> {
+        self.total_duration
+    }
+
+    pub fn get_load_duration(&self) -> Option<i64> {
+        self.load_duration
+    }
+
+    pub fn get_prompt_eval_count(&self) -> Option<i32> {
+        self.prompt_eval_count
+    }
+
+    pub fn get_prompt_eval_duration(&self) -> Option<i64> {
+        self.prompt_eval_duration
+    }
+
+    pub fn get_eval_count(&self) -> Option<i32> {
+        self.eval_count
+    }
+
+    pub fn get_eval_duration(&self) -> Option<i64> {
+        self.eval_duration
+    }
 }
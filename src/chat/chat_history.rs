@@ -0,0 +1,239 @@
+use crate::chat::chat_config::{ai_chat, ChatMessage, ChatRequest, ChatRole};
+use crate::chat::prompt_template::Prompt;
+use crate::lancevectordb::HttpsClient;
+use anyhow::{Context, Result};
+use log::debug;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Approximate token overhead per chat message for the role and separators
+const MESSAGE_TOKEN_OVERHEAD: usize = 4;
+
+const SUMMARY_SYSTEM_PROMPT: &str =
+    "You summarize conversations between a user and an AI assistant. \
+Keep the facts, decisions and open questions. Answer in plain text in less than 200 words.";
+
+/// ChatHistory keeps the conversation turns with their roles
+/// and trims the oldest turns so the prompt fits in the model context window.
+#[derive(Debug, Default)]
+pub(crate) struct ChatHistory {
+    messages: Vec<ChatMessage>,
+    summary: Option<String>,
+}
+
+impl ChatHistory {
+    pub fn new() -> Self {
+        ChatHistory::default()
+    }
+
+    pub fn push_user(&mut self, content: &str) {
+        self.messages
+            .push(ChatMessage::new(ChatRole::User, content.to_string()));
+    }
+
+    pub fn push_assistant(&mut self, content: &str) {
+        self.messages
+            .push(ChatMessage::new(ChatRole::Assistant, content.to_string()));
+    }
+
+    pub fn set_summary(&mut self, summary: String) {
+        self.summary = Some(summary);
+    }
+
+    pub fn get_summary(&self) -> Option<&String> {
+        self.summary.as_ref()
+    }
+
+    /// Messages to send to the model, the summary of trimmed turns comes first as a system message
+    pub fn messages(&self) -> Vec<ChatMessage> {
+        let mut messages = Vec::with_capacity(self.messages.len() + 1);
+        if let Some(summary) = &self.summary {
+            messages.push(ChatMessage::new(
+                ChatRole::System,
+                format!("Summary of the earlier conversation:\n{}", summary),
+            ));
+        }
+        messages.extend(self.messages.iter().cloned());
+        messages
+    }
+
+    /// Remove the oldest turns until the history fits in the token budget.
+    /// The budget is for the turns only, the prompt reserves CHAT_SUMMARY_TOKENS for the summary.
+    /// A turn is a user message and the assistant replies that follow it.
+    /// Returns the removed messages so they can be summarized.
+    pub fn fit_to_budget(&mut self, budget: usize) -> Vec<ChatMessage> {
+        let counts: Vec<usize> = self.messages.iter().map(count_message_tokens).collect();
+        let mut tokens: usize = counts.iter().sum();
+        let mut cut = 0;
+
+        while cut < self.messages.len() && tokens > budget {
+            // drop the first message and everything up to the next user message
+            tokens -= counts[cut];
+            cut += 1;
+            while cut < self.messages.len() && *self.messages[cut].get_role() != ChatRole::User {
+                tokens -= counts[cut];
+                cut += 1;
+            }
+        }
+        let removed: Vec<ChatMessage> = self.messages.drain(..cut).collect();
+
+        if !removed.is_empty() {
+            debug!(
+                "Trimmed {} messages from history to fit {} tokens",
+                removed.len(),
+                budget
+            );
+        }
+
+        removed
+    }
+}
+
+/// Count the tokens in the text using the cl100k tokenizer.
+/// Tokenizers differ between models so this is an estimate for non OpenAI models.
+pub fn count_tokens(text: &str) -> usize {
+    let bpe = tiktoken_rs::cl100k_base_singleton();
+    let bpe = bpe.lock();
+    bpe.encode_with_special_tokens(text).len()
+}
+
+/// Count the tokens of a chat message including the role overhead
+pub fn count_message_tokens(message: &ChatMessage) -> usize {
+    count_tokens(message.get_content()) + MESSAGE_TOKEN_OVERHEAD
+}
+
+/// Summarize the trimmed turns together with the previous summary
+/// # Arguments
+/// * `removed` - The messages removed from the history
+/// * `previous_summary` - The summary of turns trimmed earlier
+/// # Returns
+/// * `Result<String>` - The new summary
+pub async fn summarize_messages(
+    removed: &[ChatMessage],
+    previous_summary: Option<&String>,
+    client: &HttpsClient,
+    provider: &str,
+    api_url: &str,
+    api_key: &str,
+    ai_model: &str,
+) -> Result<String> {
+    let mut transcript = String::new();
+    if let Some(summary) = previous_summary {
+        transcript.push_str(&format!("Earlier summary: {}\n", summary));
+    }
+    for message in removed {
+        transcript.push_str(&format!("{}\n", message));
+    }
+
+    let prompt = Prompt {
+        system_message: SUMMARY_SYSTEM_PROMPT.to_string(),
+        context: None,
+        content: Vec::new(),
+        prompt: transcript,
+    };
+
+    let chat_request = ChatRequest::new(
+        provider,
+        ai_model,
+        api_url.to_string(),
+        api_key.to_string(),
+        false,
+        None,
+        None,
+        prompt,
+    );
+
+    let request = Arc::new(RwLock::new(chat_request));
+    let response = ai_chat(&request, client)
+        .await
+        .context("Failed to summarize chat history")?;
+
+    let summary = response
+        .get_message()
+        .map(|m| m.get_content().to_string())
+        .unwrap_or_default();
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tokens of the turns, what fit_to_budget compares to the budget
+    fn token_count(history: &ChatHistory) -> usize {
+        history.messages.iter().map(count_message_tokens).sum()
+    }
+
+    fn roles(messages: &[ChatMessage]) -> Vec<ChatRole> {
+        messages.iter().map(|m| m.get_role().clone()).collect()
+    }
+
+    fn conversation() -> ChatHistory {
+        let mut history = ChatHistory::new();
+        history.push_user("What does the loader do?");
+        history.push_assistant("It splits the files into chunks.");
+        history.push_assistant("And embeds every chunk.");
+        history.push_user("Where are the chunks stored?");
+        history.push_assistant("In a LanceDB table.");
+        history
+    }
+
+    #[test]
+    fn roles_survive_the_round_trip() -> Result<()> {
+        let mut history = conversation();
+        history.set_summary("The user asked about the loader.".to_string());
+
+        let messages = history.messages();
+        let json = serde_json::to_string(&messages)?;
+        let parsed: Vec<ChatMessage> = serde_json::from_str(&json)?;
+        assert_eq!(
+            roles(&parsed),
+            vec![
+                ChatRole::System,
+                ChatRole::User,
+                ChatRole::Assistant,
+                ChatRole::Assistant,
+                ChatRole::User,
+                ChatRole::Assistant,
+            ]
+        );
+        assert_eq!(parsed[1].get_content(), "What does the loader do?");
+        assert_eq!(parsed[5].get_content(), "In a LanceDB table.");
+        Ok(())
+    }
+
+    #[test]
+    fn trimming_stops_at_a_user_message() {
+        let mut history = conversation();
+        // one token less than the whole history, dropping the first message would fit
+        let budget = token_count(&history) - 1;
+
+        let removed = history.fit_to_budget(budget);
+        assert_eq!(
+            roles(&removed),
+            vec![ChatRole::User, ChatRole::Assistant, ChatRole::Assistant]
+        );
+        assert_eq!(
+            roles(&history.messages()),
+            vec![ChatRole::User, ChatRole::Assistant]
+        );
+        assert!(token_count(&history) <= budget);
+
+        let removed = history.fit_to_budget(0);
+        assert_eq!(removed.len(), 2);
+        assert!(history.messages().is_empty());
+    }
+
+    #[test]
+    fn the_summary_does_not_count_toward_the_budget() {
+        let mut history = conversation();
+        let tokens = token_count(&history);
+        history.set_summary("A long summary of the earlier conversation. ".repeat(50));
+        assert_eq!(token_count(&history), tokens);
+
+        let removed = history.fit_to_budget(tokens);
+        assert!(removed.is_empty());
+        assert_eq!(history.messages().len(), 6);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::lancevectordb::query::QueryRecord;
+
+const CITATION_INSTRUCTION: &str = "Answer using the numbered sources below. \
+Cite the sources you use with their number in square brackets like [1] or [2, 3]. \
+Do not cite sources you did not use.";
+
+/// Source is a retrieved chunk of a file numbered for citation
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub number: usize,
+    pub path: String,
+    pub first_chunk: Option<i32>,
+    pub last_chunk: Option<i32>,
+    pub start_line: Option<i32>,
+    pub end_line: Option<i32>,
+    pub score: Option<f32>,
+    content: Vec<(Option<i32>, String)>,
+}
+
+impl Source {
+    /// Location of the source like `src/main.rs:10-24`
+    pub fn location(&self) -> String {
+        match (self.start_line, self.end_line) {
+            (Some(start), Some(end)) if start != end => format!("{}:{}-{}", self.path, start, end),
+            (Some(start), _) => format!("{}:{}", self.path, start),
+            _ => self.path.clone(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        let mut description = format!("[{}] {}", self.number, self.location());
+        match (self.first_chunk, self.last_chunk) {
+            (Some(first), Some(last)) if first != last => {
+                description.push_str(&format!(" (chunks {}-{})", first, last))
+            }
+            (Some(first), _) => description.push_str(&format!(" (chunk {})", first)),
+            _ => {}
+        }
+        description
+    }
+
+    fn content(&self) -> String {
+        self.content
+            .iter()
+            .map(|(_, line)| line.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Citations numbers the retrieved chunks so the answer can be traced back to the code
+#[derive(Debug, Clone, Default)]
+pub struct Citations {
+    sources: Vec<Source>,
+    show_uncited: bool,
+}
+
+impl Citations {
+    /// Number the context blocks, every block is one source
+    pub fn from_blocks(blocks: &[Vec<QueryRecord>]) -> Citations {
+        let sources = blocks
+            .iter()
+            .filter(|block| !block.is_empty())
+            .enumerate()
+            .map(|(i, block)| {
+                let mut lines: Vec<(Option<i32>, String)> = block
+                    .iter()
+                    .map(|r| (r.start_line, r.content.clone()))
+                    .collect();
+                lines.sort_by_key(|(line, _)| *line);
+
+                Source {
+                    number: i + 1,
+                    path: block[0].path().to_string(),
+                    first_chunk: block.iter().filter_map(|r| r.chunk_number).min(),
+                    last_chunk: block.iter().filter_map(|r| r.chunk_number).max(),
+                    start_line: block.iter().filter_map(|r| r.start_line).min(),
+                    end_line: block.iter().filter_map(|r| r.end_line).max(),
+                    score: block
+                        .iter()
+                        .filter_map(|r| r.score())
+                        .max_by(|a, b| a.total_cmp(b)),
+                    content: lines,
+                }
+            })
+            .collect();
+
+        Citations {
+            sources,
+            show_uncited: false,
+        }
+    }
+
+    /// List the retrieved sources the answer does not cite as well
+    pub fn with_show_uncited(mut self, show_uncited: bool) -> Self {
+        self.show_uncited = show_uncited;
+        self
+    }
+
+    /// The context for the model with the citation instruction and the numbered sources
+    pub fn context(&self) -> String {
+        let mut context = format!("{}\n", CITATION_INSTRUCTION);
+        for source in &self.sources {
+            context.push_str(&format!("\n{}\n{}\n", source.describe(), source.content()));
+        }
+        context
+    }
+
+    /// The distinct file paths of the sources in retrieval order
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = Vec::new();
+        for source in &self.sources {
+            if !paths.contains(&source.path) {
+                paths.push(source.path.clone());
+            }
+        }
+        paths
+    }
+
+    /// List the sources cited in the answer with their paths and similarity scores
+    /// # Arguments
+    /// * `answer` - The answer of the model
+    /// # Returns
+    /// * `String` - The source listing
+    pub fn format_sources(&self, answer: &str) -> String {
+        let cited = cited_numbers(answer);
+        let (cited_sources, uncited_sources): (Vec<&Source>, Vec<&Source>) =
+            self.sources.iter().partition(|s| cited.contains(&s.number));
+
+        let mut listing = String::new();
+        if cited_sources.is_empty() {
+            listing.push_str("No sources cited\n");
+        } else {
+            listing.push_str("Sources:\n");
+            for source in cited_sources {
+                listing.push_str(&format_source(source));
+            }
+        }
+
+        if self.show_uncited && !uncited_sources.is_empty() {
+            listing.push_str("Retrieved but not cited:\n");
+            for source in uncited_sources {
+                listing.push_str(&format_source(source));
+            }
+        }
+
+        listing
+    }
+}
+
+fn format_source(source: &Source) -> String {
+    match source.score {
+        Some(score) => format!("  {} score: {:.3}\n", source.describe(), score),
+        None => format!("  {}\n", source.describe()),
+    }
+}
+
+/// The source numbers cited in the text like `[1]` or `[2, 3]`
+fn cited_numbers(text: &str) -> Vec<usize> {
+    let mut numbers = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('[') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find(']') else {
+            break;
+        };
+        let parsed: Option<Vec<usize>> = rest[..end]
+            .split(',')
+            .map(|n| n.trim().parse::<usize>().ok())
+            .collect();
+        if let Some(parsed) = parsed {
+            for number in parsed {
+                if !numbers.contains(&number) {
+                    numbers.push(number);
+                }
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+
+    numbers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_and_grouped_citations() {
+        assert_eq!(cited_numbers("see [1] and [2, 3]"), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn repeated_citations_are_listed_once() {
+        assert_eq!(cited_numbers("[2] then [1] and [2,1]"), vec![2, 1]);
+    }
+
+    #[test]
+    fn groups_with_other_text_are_not_citations() {
+        assert_eq!(cited_numbers("[1, x] and [3]"), vec![3]);
+        assert_eq!(cited_numbers("let v = a[i]; [] [-1]"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn unclosed_brackets_end_the_search() {
+        assert_eq!(cited_numbers("[4] and [5"), vec![4]);
+    }
+}
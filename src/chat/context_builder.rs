@@ -0,0 +1,212 @@
+use crate::chat::chat_history::count_tokens;
+use crate::lancevectordb::query::QueryRecord;
+use log::debug;
+
+/// Approximate tokens for the source marker of a block
+const BLOCK_TOKEN_OVERHEAD: usize = 16;
+
+/// ContextBuilder turns the retrieved rows into the blocks sent to the model.
+/// Rows repeated by the chunk overlap are removed, adjacent chunks of a file are merged
+/// into one block, the blocks are ordered by score and packed within the token budget.
+pub(crate) struct ContextBuilder {
+    token_budget: usize,
+}
+
+impl ContextBuilder {
+    pub fn new(token_budget: usize) -> ContextBuilder {
+        ContextBuilder { token_budget }
+    }
+
+    /// Build the context blocks from the retrieved rows
+    /// # Arguments
+    /// * `records` - The retrieved rows in retrieval order
+    /// # Returns
+    /// * `Vec<Vec<QueryRecord>>` - The blocks, best score first, each block is one file range in line order
+    pub fn build(&self, records: &[QueryRecord]) -> Vec<Vec<QueryRecord>> {
+        let mut blocks: Vec<Vec<QueryRecord>> = Vec::new();
+        for file_records in group_by_file(records) {
+            blocks.extend(merge_adjacent(dedup(file_records)));
+        }
+
+        // stable sort keeps the retrieval order for rows without a score
+        blocks.sort_by(|a, b| {
+            let a = block_score(a).unwrap_or(f32::MIN);
+            let b = block_score(b).unwrap_or(f32::MIN);
+            b.total_cmp(&a)
+        });
+
+        let mut used = 0;
+        let mut packed = Vec::new();
+        let total = blocks.len();
+        for block in blocks {
+            let tokens = block_tokens(&block);
+            // skip blocks that do not fit, a smaller block further down may still fit
+            if used + tokens > self.token_budget {
+                continue;
+            }
+            used += tokens;
+            packed.push(block);
+        }
+
+        debug!(
+            "Packed {} of {} context blocks in {} of {} tokens",
+            packed.len(),
+            total,
+            used,
+            self.token_budget
+        );
+        packed
+    }
+}
+
+/// Group the rows by file keeping the order the files were first retrieved in
+fn group_by_file(records: &[QueryRecord]) -> Vec<Vec<QueryRecord>> {
+    let mut files: Vec<Vec<QueryRecord>> = Vec::new();
+    for record in records {
+        match files.iter_mut().find(|f| f[0].path() == record.path()) {
+            Some(file) => file.push(record.clone()),
+            None => files.push(vec![record.clone()]),
+        }
+    }
+    files
+}
+
+/// Remove the rows repeated by the chunk overlap, the same line of a file is kept once
+/// with the best distance. Rows without line numbers are compared by content.
+fn dedup(records: Vec<QueryRecord>) -> Vec<QueryRecord> {
+    let mut unique: Vec<QueryRecord> = Vec::with_capacity(records.len());
+    for record in records {
+        let existing = unique
+            .iter_mut()
+            .find(|r| match (r.start_line, record.start_line) {
+                (Some(a), Some(b)) => a == b,
+                _ => r.content == record.content && !r.content.trim().is_empty(),
+            });
+        match existing {
+            Some(existing) => {
+                if record.distance.unwrap_or(f32::MAX) < existing.distance.unwrap_or(f32::MAX) {
+                    existing.distance = record.distance;
+                    existing.similarity = record.similarity;
+                }
+            }
+            None => unique.push(record),
+        }
+    }
+    unique
+}
+
+/// Merge the rows of adjacent chunks, chunk n and n + 1 of a file become one block
+fn merge_adjacent(mut records: Vec<QueryRecord>) -> Vec<Vec<QueryRecord>> {
+    records.sort_by_key(|r| (r.chunk_number, r.start_line));
+
+    let mut blocks: Vec<Vec<QueryRecord>> = Vec::new();
+    for record in records {
+        let adjacent = blocks.last().and_then(|b| b.last()).is_some_and(|last| {
+            match (last.chunk_number, record.chunk_number) {
+                (Some(a), Some(b)) => b - a <= 1,
+                _ => false,
+            }
+        });
+        match blocks.last_mut() {
+            Some(block) if adjacent => block.push(record),
+            _ => blocks.push(vec![record]),
+        }
+    }
+    blocks
+}
+
+fn block_score(block: &[QueryRecord]) -> Option<f32> {
+    block
+        .iter()
+        .filter_map(|r| r.score())
+        .max_by(|a, b| a.total_cmp(b))
+}
+
+fn block_tokens(block: &[QueryRecord]) -> usize {
+    block
+        .iter()
+        .map(|r| count_tokens(&r.content) + 1)
+        .sum::<usize>()
+        + BLOCK_TOKEN_OVERHEAD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(chunk: i32, line: Option<i32>, content: &str, distance: f32) -> QueryRecord {
+        QueryRecord {
+            id: None,
+            content: content.to_string(),
+            metadata: "main.rs".to_string(),
+            chunk_number: Some(chunk),
+            distance: Some(distance),
+            similarity: Some(1.0 - distance),
+            file_path: Some("src/main.rs".to_string()),
+            start_line: line,
+            end_line: line,
+            rerank_score: None,
+        }
+    }
+
+    #[test]
+    fn overlapping_lines_are_kept_once_with_the_best_distance() {
+        let records = vec![
+            record(0, Some(4), "let a = 1;", 0.4),
+            record(0, Some(5), "let b = 2;", 0.4),
+            record(1, Some(5), "let b = 2;", 0.2),
+            record(1, Some(6), "let c = 3;", 0.2),
+        ];
+        let unique = dedup(records);
+
+        let lines: Vec<Option<i32>> = unique.iter().map(|r| r.start_line).collect();
+        assert_eq!(lines, vec![Some(4), Some(5), Some(6)]);
+        assert_eq!(unique[1].distance, Some(0.2));
+        assert_eq!(unique[1].score(), Some(0.8));
+    }
+
+    #[test]
+    fn rows_without_lines_are_compared_by_content() {
+        let records = vec![
+            record(0, None, "fn main() {}", 0.3),
+            record(1, None, "fn main() {}", 0.1),
+            record(1, None, "", 0.1),
+            record(2, None, "", 0.1),
+        ];
+        let unique = dedup(records);
+
+        // empty rows are not merged since they say nothing about being the same line
+        assert_eq!(unique.len(), 3);
+        assert_eq!(unique[0].distance, Some(0.1));
+    }
+
+    #[test]
+    fn adjacent_chunks_are_merged_into_one_block() {
+        let records = vec![
+            record(3, Some(30), "c", 0.1),
+            record(0, Some(1), "a", 0.1),
+            record(1, Some(10), "b", 0.1),
+            record(5, Some(50), "d", 0.1),
+        ];
+        let blocks = merge_adjacent(records);
+
+        let chunks: Vec<Vec<Option<i32>>> = blocks
+            .iter()
+            .map(|b| b.iter().map(|r| r.chunk_number).collect())
+            .collect();
+        assert_eq!(
+            chunks,
+            vec![vec![Some(0), Some(1)], vec![Some(3)], vec![Some(5)]]
+        );
+    }
+
+    #[test]
+    fn rows_of_one_chunk_stay_in_line_order() {
+        let records = vec![record(2, Some(22), "b", 0.1), record(2, Some(21), "a", 0.1)];
+        let blocks = merge_adjacent(records);
+
+        assert_eq!(blocks.len(), 1);
+        let lines: Vec<Option<i32>> = blocks[0].iter().map(|r| r.start_line).collect();
+        assert_eq!(lines, vec![Some(21), Some(22)]);
+    }
+}
@@ -1,29 +1,53 @@
-use crate::app::constants::CHAT_RESPONSE_FORMAT;
-use crate::chat::chat_config::{ai_chat, ChatMessage};
+use crate::app::constants::{
+    CHAT_CONTEXT_WINDOW, CHAT_FORMAT_RETRIES, CHAT_RESPONSE_TOKENS, CHAT_SUMMARY_TOKENS,
+    RAG_CONTEXT_SHARE,
+};
+use crate::chat::chat_config::{ai_chat, ChatMessage, ChatRequest, ChatRole};
+use crate::chat::chat_history::{count_tokens, ChatHistory};
+use crate::chat::citations::Citations;
+use crate::chat::model_options::Options;
+use crate::chat::prompt_template::{Prompt, PromptTemplate};
+use crate::chat::response_format::ResponseFormat;
+use crate::chat::tools::ToolContext;
 use crate::lancevectordb::HttpsClient;
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use chat_config::ChatResponse;
-use log::{debug, info};
+use log::{debug, info, warn};
 use serde_json::Value;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+pub(crate) mod agentic;
 mod chat_config;
+mod chat_history;
+pub(crate) mod citations;
+pub(crate) mod context_builder;
 #[allow(dead_code)]
-mod model_options;
-mod prompt_template;
+pub(crate) mod model_options;
+pub(crate) mod prompt_library;
+pub(crate) mod prompt_template;
+pub(crate) mod reranker;
+pub(crate) mod response_format;
+pub(crate) mod session;
+pub(crate) mod tools;
 
 /// Run the chatbot
 /// # Arguments
 /// * `ai_prompt` - The prompt to send to the AI model
 /// * `context` - The context to send to the AI model
+/// * `template` - The template to render the user message with
+/// * `format` - The format the model answers in
+/// * `options` - The generation options like temperature and seed
 /// # Returns
 /// * `Result<()>` - The result of the chatbot
 pub async fn run_chat(
     system_prompt: &str,
     ai_prompt: &str,
     context: Option<&str>,
+    template: Option<&PromptTemplate>,
+    format: &ResponseFormat,
+    options: &Options,
     client: &HttpsClient,
     provider: &str,
     api_url: &str,
@@ -32,54 +56,46 @@ pub async fn run_chat(
 ) -> anyhow::Result<ChatResponse> {
     info!("Starting LLM chat...");
 
-    let cm = ChatMessage::new(
-        chat_config::ChatRole::User,
-        context.map(|s| s.to_string()).unwrap_or("".to_string()),
-    );
-    let contexts = vec![Some(cm)];
-
-    let prompt = prompt_template::Prompt::new(system_prompt, &contexts, ai_prompt)
+    let prompt = prompt_template::Prompt::new(system_prompt, context, &[], ai_prompt)
         .await
         .context("Failed to create prompt")?;
+    let prompt = apply_template(prompt, template).context("Failed to apply template")?;
 
     debug!("Prompt: {:?}", prompt);
 
-    // @TODO: Implement the template
-    // let template = prompt_template::get_template(&prompt, PROMPT_TEMPLATE_PATH)
-    //     .context("Failed to get template")?;
-
-    let chat_url = format!("{}/{}", api_url, "api/chat");
-
     let chat_request = chat_config::ChatRequest::new(
         provider,
         ai_model,
-        chat_url,
+        api_url.to_string(),
         api_key.to_string(),
         false,
-        CHAT_RESPONSE_FORMAT.to_string(),
-        None,
+        Some(format.clone()),
+        Some(options.clone()),
         prompt,
     );
 
-    // Create a new Arc<RwLock<ChatRequest>> to share the request between threads
-    let request = Arc::new(RwLock::new(chat_request));
-
     // Call the AI chat API
-    let response = ai_chat(&request, client)
+    let response = send_chat(chat_request, format, None, client)
         .await
         .context("Failed to get ai chat response")?;
 
     if let Some(m) = response.get_message() {
-        println!("AI Response: {}", m.get_content())
+        print_response(m.get_content(), format)?;
     }
 
     Ok(response)
 }
 
-/// Run the chatbot with history
+/// Run the chatbot with history, every turn is saved to the chat session
 /// # Arguments
 /// * `initial_prompt` - The initial prompt to start the chat
 /// * `context` - The context to send to the AI model
+/// * `citations` - The numbered sources of the context, listed after every answer
+/// * `session_id` - The saved session to resume, a new session is started if None
+/// * `template` - The template to render the user message with
+/// * `format` - The format the model answers in
+/// * `options` - The generation options, `num_ctx` sets the history token budget
+/// * `tools` - The built-in tools the model can call, tools are disabled if None
 /// * `client` - The HTTP client to use for requests
 /// # Returns
 /// * `Result<()>` - The result of the chatbot
@@ -87,6 +103,12 @@ pub async fn run_chat_with_history(
     system_prompt: &str,
     initial_prompt: &str,
     context: Option<&str>,
+    citations: Option<&Citations>,
+    session_id: Option<&str>,
+    template: Option<&PromptTemplate>,
+    format: &ResponseFormat,
+    options: &Options,
+    tools: Option<&ToolContext>,
     client: &HttpsClient,
     provider: &str,
     api_url: &str,
@@ -95,20 +117,70 @@ pub async fn run_chat_with_history(
 ) -> anyhow::Result<()> {
     println!("Starting LLM chat with history...");
 
-    let mut history = Vec::new();
-    let query_content = ChatMessage::new(
-        chat_config::ChatRole::User,
-        context.map(|s| s.to_string()).unwrap_or("".to_string()),
-    );
-    history.push(Some(query_content));
+    let mut history = ChatHistory::new();
+    let mut session = match session_id {
+        Some(id) => {
+            let (session, turns) = session::ChatSession::open(id)
+                .await
+                .context("Failed to open chat session")?;
+            for turn in turns {
+                history.push_user(&turn.question);
+                history.push_assistant(&turn.answer);
+            }
+            session
+        }
+        None => session::ChatSession::new().context("Failed to create chat session")?,
+    };
+    println!("Chat session: {}", session.get_id());
+
+    let context_window = context_window(options);
+    let response_tokens = response_tokens(options);
+
     let mut current_prompt = initial_prompt.to_string();
 
     loop {
-        let prompt = prompt_template::Prompt::new(system_prompt, &history, &current_prompt)
+        // Trim the oldest turns so system prompt, context, history and question fit the context window
+        let prompt = prompt_template::Prompt::new(
+            system_prompt,
+            context,
+            &history.messages(),
+            &current_prompt,
+        )
+        .await
+        .context("Failed to create prompt")?;
+        let prompt = apply_template(prompt, template).context("Failed to apply template")?;
+
+        // the summary is covered by CHAT_SUMMARY_TOKENS, the budget is for the turns
+        let fixed_tokens = count_tokens(&prompt.system_message)
+            + prompt.context.as_deref().map_or(0, count_tokens)
+            + count_tokens(&prompt.prompt);
+        let budget =
+            context_window.saturating_sub(response_tokens + CHAT_SUMMARY_TOKENS + fixed_tokens);
+
+        let removed = history.fit_to_budget(budget);
+        let prompt = if removed.is_empty() {
+            prompt
+        } else {
+            match chat_history::summarize_messages(
+                &removed,
+                history.get_summary(),
+                client,
+                provider,
+                api_url,
+                api_key,
+                ai_model,
+            )
             .await
-            .context("Failed to create prompt")?;
+            {
+                Ok(summary) => history.set_summary(summary),
+                Err(e) => warn!("Dropping trimmed history without summary: {:?}", e),
+            }
 
-        let options = model_options::OptionsBuilder::new().num_ctx(128000).build();
+            prompt_template::Prompt {
+                content: history.messages(),
+                ..prompt
+            }
+        };
 
         let chat_request = chat_config::ChatRequest::new(
             provider,
@@ -116,36 +188,35 @@ pub async fn run_chat_with_history(
             api_url.to_string(),
             api_key.to_string(),
             false,
-            CHAT_RESPONSE_FORMAT.to_string(),
-            Some(options),
+            Some(format.clone()),
+            Some(options.clone()),
             prompt,
         );
 
         debug!("Chat Content with history: {:?}", chat_request);
 
-        let request = Arc::new(RwLock::new(chat_request));
-
-        let response = ai_chat(&request, client)
+        let response = send_chat(chat_request, format, tools, client)
             .await
             .context("Failed to get AI chat response")?;
 
         // response.print_message();
 
+        let sources = citations.map(|c| c.paths()).unwrap_or_default();
+        let turn = session.new_turn(provider, &current_prompt, &sources, &response);
+        if let Err(e) = session.append(&turn).await {
+            warn!("Failed to save chat session turn: {:?}", e);
+        }
+
+        history.push_user(&current_prompt);
+
         let ai_message = response.get_message();
         if let Some(message) = ai_message {
             let content = message.get_content();
-            let chat_history = ChatMessage::new(chat_config::ChatRole::User, content.to_string());
-            history.push(Some(chat_history));
-
-            // Parse the JSON string into a serde_json::Value
-            let json_value: Value = serde_json::from_str(content)
-                .with_context(|| format!("Failed to parse JSON: {}", content))?;
-
-            // Pretty-print the JSON with indentation
-            let pretty_json =
-                serde_json::to_string_pretty(&json_value).context("Failed to pretty print JSON")?;
-
-            println!("AI Response: {}", pretty_json);
+            history.push_assistant(content);
+            print_response(content, format)?;
+            if let Some(citations) = citations {
+                println!("{}", citations.format_sources(content));
+            }
         } else {
             println!("AI Response: None");
         }
@@ -166,3 +237,151 @@ pub async fn run_chat_with_history(
 
     Ok(())
 }
+
+/// Token budget for the retrieved context, a share of the context window left after the response
+/// so the system prompt, history and question still fit
+pub fn context_token_budget(options: &Options) -> usize {
+    context_window(options).saturating_sub(response_tokens(options)) * RAG_CONTEXT_SHARE / 100
+}
+
+fn context_window(options: &Options) -> usize {
+    options.get_num_ctx().unwrap_or(CHAT_CONTEXT_WINDOW) as usize
+}
+
+/// Tokens reserved for the response, `num_predict` when it limits the response
+fn response_tokens(options: &Options) -> usize {
+    options
+        .get_num_predict()
+        .filter(|n| *n > 0)
+        .map_or(CHAT_RESPONSE_TOKENS, |n| n as usize)
+}
+
+/// Render the user message with the template, the template places the context itself
+fn apply_template(prompt: Prompt, template: Option<&PromptTemplate>) -> anyhow::Result<Prompt> {
+    let Some(template) = template else {
+        return Ok(prompt);
+    };
+
+    let rendered = template.render(
+        &prompt.system_message,
+        &prompt.prompt,
+        prompt.context.as_deref().unwrap_or(""),
+    )?;
+
+    Ok(Prompt {
+        context: None,
+        prompt: rendered,
+        ..prompt
+    })
+}
+
+/// Send the chat request, run the tool calls of the model
+/// and ask the model again when the response does not match the format
+/// # Arguments
+/// * `chat_request` - The chat request to send
+/// * `format` - The format the response is validated against
+/// * `tools` - The tools the model can call
+/// * `client` - The HTTP client to use for requests
+/// # Returns
+/// * `Result<ChatResponse>` - The first answer that matches the format
+async fn send_chat(
+    chat_request: ChatRequest,
+    format: &ResponseFormat,
+    tools: Option<&ToolContext>,
+    client: &HttpsClient,
+) -> anyhow::Result<ChatResponse> {
+    let chat_request = match tools {
+        Some(tools) => chat_request.with_tools(tools.definitions()),
+        None => chat_request,
+    };
+
+    // Create a new Arc<RwLock<ChatRequest>> to share the request between threads
+    let request = Arc::new(RwLock::new(chat_request));
+    let mut retries = 0;
+    let mut tool_rounds = 0;
+
+    loop {
+        let response = ai_chat(&request, client).await?;
+
+        if let (Some(tools), Some(message)) = (tools, response.get_message()) {
+            if !message.get_tool_calls().is_empty() {
+                tool_rounds += 1;
+                let mut results = Vec::new();
+                for call in message.get_tool_calls() {
+                    let result = tools.execute(call).await;
+                    results.push(ChatMessage::tool_result(call, result));
+                }
+
+                let mut request = request.write().await;
+                request.messages.push(message.clone());
+                request.messages.extend(results);
+                if tool_rounds >= tools.get_max_rounds() {
+                    // the model has to answer with the context it has
+                    warn!("Reached {} tool rounds, disabling tools", tool_rounds);
+                    request.tools = None;
+                }
+                continue;
+            }
+        }
+
+        let content = response
+            .get_message()
+            .map(|m| m.get_content().as_str())
+            .unwrap_or_default();
+
+        let errors = format.validate(content);
+        if errors.is_empty() {
+            return Ok(response);
+        }
+        if retries >= CHAT_FORMAT_RETRIES {
+            return Err(anyhow!(
+                "Response does not match the format after {} retries: {}",
+                retries,
+                errors.join("; ")
+            ));
+        }
+
+        retries += 1;
+        warn!(
+            "Response does not match the format, retry {}: {}",
+            retries,
+            errors.join("; ")
+        );
+
+        // Show the model its answer and the violations so it can correct them
+        let mut request = request.write().await;
+        request
+            .messages
+            .push(ChatMessage::new(ChatRole::Assistant, content.to_string()));
+        request.messages.push(ChatMessage::new(
+            ChatRole::User,
+            format!(
+                "The response is invalid: {}. Answer again with only JSON that matches the requested format.",
+                errors.join("; ")
+            ),
+        ));
+    }
+}
+
+/// Print the response, JSON is pretty printed and text is rendered as Markdown
+/// Text is printed as is when stdout is redirected, the ANSI styles would end up in the output
+fn print_response(content: &str, format: &ResponseFormat) -> anyhow::Result<()> {
+    if !format.is_json() {
+        println!(
+            "AI Response:\n{}",
+            response_format::render_text(content, std::io::stdout().is_terminal())
+        );
+        return Ok(());
+    }
+
+    // Parse the JSON string into a serde_json::Value
+    let json_value: Value = serde_json::from_str(content)
+        .with_context(|| format!("Failed to parse JSON: {}", content))?;
+
+    // Pretty-print the JSON with indentation
+    let pretty_json =
+        serde_json::to_string_pretty(&json_value).context("Failed to pretty print JSON")?;
+
+    println!("AI Response: {}", pretty_json);
+    Ok(())
+}
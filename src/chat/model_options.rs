@@ -1,6 +1,11 @@
+use crate::app::config::AppConfig;
+use crate::app::constants::CHAT_CONTEXT_WINDOW;
+use anyhow::{anyhow, Context, Result};
+use serde_json::{Map, Value};
+
 #[allow(dead_code)]
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
-
+#[serde(deny_unknown_fields)]
 pub struct Options {
     #[serde(skip_serializing_if = "Option::is_none")]
     num_keep: Option<i32>,
@@ -208,3 +213,246 @@ impl OptionsBuilder {
         self.options
     }
 }
+
+impl Options {
+    pub fn get_num_ctx(&self) -> Option<i32> {
+        self.num_ctx
+    }
+
+    pub fn get_num_predict(&self) -> Option<i32> {
+        self.num_predict
+    }
+
+    /// Load the options from a JSON file with the Ollama option names
+    /// # Arguments
+    /// * `path` - Path to the options file
+    /// # Returns
+    /// * `Result<Options>` - The options in the file
+    pub fn from_file(path: &str) -> Result<Options> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read options file: {}", path))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse options file: {}", path))
+    }
+
+    /// Merge the options, the options set in `other` replace the options in self
+    pub fn merge(self, other: &Options) -> Result<Options> {
+        let mut merged = to_map(&self)?;
+        merged.extend(to_map(other)?);
+        serde_json::from_value(Value::Object(merged)).context("Failed to merge model options")
+    }
+
+    /// Check the options are in the ranges the models accept
+    pub fn validate(&self) -> Result<()> {
+        check_range("temperature", self.temperature, 0.0, 2.0)?;
+        check_range("top_p", self.top_p, 0.0, 1.0)?;
+        check_range("min_p", self.min_p, 0.0, 1.0)?;
+        check_range("typical_p", self.typical_p, 0.0, 1.0)?;
+        check_range("repeat_penalty", self.repeat_penalty, 0.0, 2.0)?;
+        check_range("presence_penalty", self.presence_penalty, -2.0, 2.0)?;
+        check_range("frequency_penalty", self.frequency_penalty, -2.0, 2.0)?;
+        check_range("mirostat_tau", self.mirostat_tau, 0.0, 10.0)?;
+        check_range("mirostat_eta", self.mirostat_eta, 0.0, 1.0)?;
+        check_range("top_k", self.top_k, 0, i32::MAX)?;
+        check_range("mirostat", self.mirostat, 0, 2)?;
+        check_range("num_ctx", self.num_ctx, 1, i32::MAX)?;
+        // -1 generates until the model stops, -2 until the context is full
+        check_range("num_predict", self.num_predict, -2, i32::MAX)?;
+
+        if let Some(stop) = &self.stop {
+            if stop.iter().any(|s| s.is_empty()) {
+                return Err(anyhow!("stop sequences must not be empty"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The options the OpenAI chat API accepts as top level fields of the request
+    pub fn openai_fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        let mut insert = |name: &str, value: Option<Value>| {
+            if let Some(value) = value {
+                fields.insert(name.to_string(), value);
+            }
+        };
+        insert("temperature", self.temperature.map(Value::from));
+        insert("top_p", self.top_p.map(Value::from));
+        insert("seed", self.seed.map(Value::from));
+        insert("presence_penalty", self.presence_penalty.map(Value::from));
+        insert("frequency_penalty", self.frequency_penalty.map(Value::from));
+        insert("stop", self.stop.clone().map(Value::from));
+        insert(
+            "max_tokens",
+            self.num_predict.filter(|n| *n > 0).map(Value::from),
+        );
+        fields
+    }
+}
+
+/// Resolve the options for a chat with the model
+/// The built-in defaults are replaced by the model defaults in the config file,
+/// then by the options file and last by the command line options.
+/// # Arguments
+/// * `ai_model` - The model the options are for
+/// * `options_file` - Optional JSON file with options
+/// * `cli_options` - The options set on the command line
+/// # Returns
+/// * `Result<Options>` - The validated options
+pub fn resolve_options(
+    ai_model: &str,
+    options_file: Option<&str>,
+    cli_options: &Options,
+) -> Result<Options> {
+    let config = AppConfig::load().context("Failed to load config")?;
+    resolve_with_config(&config, ai_model, options_file, cli_options)
+}
+
+/// Resolve the options with the model defaults of the config
+fn resolve_with_config(
+    config: &AppConfig,
+    ai_model: &str,
+    options_file: Option<&str>,
+    cli_options: &Options,
+) -> Result<Options> {
+    let mut options = OptionsBuilder::new().num_ctx(CHAT_CONTEXT_WINDOW).build();
+
+    if let Some(model_options) = config.model_options(ai_model) {
+        options = options.merge(model_options)?;
+    }
+
+    if let Some(path) = options_file {
+        options = options.merge(&Options::from_file(path)?)?;
+    }
+
+    let options = options.merge(cli_options)?;
+    options.validate().context("Invalid model options")?;
+
+    Ok(options)
+}
+
+fn to_map(options: &Options) -> Result<Map<String, Value>> {
+    match serde_json::to_value(options).context("Failed to serialize model options")? {
+        Value::Object(map) => Ok(map),
+        _ => Err(anyhow!("Model options are not a JSON object")),
+    }
+}
+
+fn check_range<T: PartialOrd + std::fmt::Display>(
+    name: &str,
+    value: Option<T>,
+    min: T,
+    max: T,
+) -> Result<()> {
+    match value {
+        Some(value) if value < min || value > max => Err(anyhow!(
+            "{} must be between {} and {}, got {}",
+            name,
+            min,
+            max,
+            value
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write the options file in its own temp path
+    fn options_file(name: &str, content: &str) -> Result<String> {
+        let path = std::env::temp_dir().join(format!(
+            "vector-embed-test-{}-{}.json",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, content)?;
+        Ok(path.display().to_string())
+    }
+
+    fn config(model: &str, options: Options) -> AppConfig {
+        AppConfig {
+            models: [(model.to_string(), options)].into_iter().collect(),
+            ..AppConfig::default()
+        }
+    }
+
+    #[test]
+    fn out_of_range_options_are_rejected() {
+        let error = OptionsBuilder::new().temperature(2.5).build().validate();
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "temperature must be between 0 and 2, got 2.5"
+        );
+        assert!(OptionsBuilder::new()
+            .top_p(-0.1)
+            .build()
+            .validate()
+            .is_err());
+        assert!(OptionsBuilder::new().top_p(1.1).build().validate().is_err());
+        assert!(OptionsBuilder::new()
+            .stop(vec![String::new()])
+            .build()
+            .validate()
+            .is_err());
+
+        let options = OptionsBuilder::new().temperature(2.0).top_p(0.0).build();
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_keys_in_the_options_file_are_rejected() -> Result<()> {
+        let path = options_file(
+            "unknown-key",
+            r#"{ "temperature": 0.3, "temprature": 0.5 }"#,
+        )?;
+        let error = Options::from_file(&path).unwrap_err();
+        assert!(format!("{:#}", error).contains("unknown field `temprature`"));
+
+        let error =
+            resolve_with_config(&AppConfig::default(), "m", Some(&path), &Options::default());
+        assert!(error.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cli_options_override_the_file_and_the_config() -> Result<()> {
+        let config = config(
+            "qwen2:7b",
+            OptionsBuilder::new()
+                .temperature(0.2)
+                .top_p(0.5)
+                .seed(1)
+                .num_ctx(16384)
+                .build(),
+        );
+        let path = options_file("override", r#"{ "temperature": 0.4, "top_p": 0.6 }"#)?;
+        let cli_options = OptionsBuilder::new().temperature(0.8).build();
+
+        let options = resolve_with_config(&config, "qwen2:7b", Some(&path), &cli_options)?;
+        assert_eq!(options.temperature, Some(0.8));
+        assert_eq!(options.top_p, Some(0.6));
+        assert_eq!(options.seed, Some(1));
+        assert_eq!(options.num_ctx, Some(16384));
+
+        // the config of another model is not used, the built-in context window is
+        let options = resolve_with_config(&config, "llama3", None, &cli_options)?;
+        assert_eq!(options.seed, None);
+        assert_eq!(options.num_ctx, Some(CHAT_CONTEXT_WINDOW));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_merged_options_are_rejected() -> Result<()> {
+        let path = options_file("invalid", r#"{ "top_p": 1.5 }"#)?;
+        let error =
+            resolve_with_config(&AppConfig::default(), "m", Some(&path), &Options::default())
+                .unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "Invalid model options: top_p must be between 0 and 1, got 1.5"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use crate::app::config::app_config_dir;
+use crate::app::constants::PROMPTS_DIR;
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// System prompts embedded in the binary so it works from any directory
+const BUILT_IN_PROMPTS: &[(&str, &str)] = &[
+    ("rag", include_str!("../../template/rag_prompt.txt")),
+    ("general", include_str!("../../template/general_prompt.txt")),
+    (
+        "software-engineer",
+        include_str!("../../template/software-engineer.txt"),
+    ),
+    (
+        "software-engineer-long",
+        include_str!("../../template/software-engineer-long.txt"),
+    ),
+    (
+        "spark-engineer",
+        include_str!("../../template/spark-engineer.txt"),
+    ),
+    ("spark", include_str!("../../template/spark_prompt.txt")),
+];
+
+/// PromptSource tells where a named prompt is loaded from
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptSource {
+    BuiltIn,
+    /// A user prompt in the config directory, overrides the built-in prompt with the same name
+    User(PathBuf),
+}
+
+/// PromptEntry is a named prompt shown by `prompts list`
+#[derive(Debug, Clone)]
+pub struct PromptEntry {
+    pub name: String,
+    pub source: PromptSource,
+}
+
+/// Get the directory for user prompts `<config dir>/prompts`, files are named `<name>.txt`
+pub fn user_prompts_dir() -> Result<PathBuf> {
+    Ok(app_config_dir()?.join(PROMPTS_DIR))
+}
+
+fn user_prompt_path(name: &str) -> Option<PathBuf> {
+    let path = user_prompts_dir().ok()?.join(format!("{}.txt", name));
+    path.is_file().then_some(path)
+}
+
+fn built_in_prompt(name: &str) -> Option<&'static str> {
+    BUILT_IN_PROMPTS
+        .iter()
+        .find(|(prompt_name, _)| *prompt_name == name)
+        .map(|(_, prompt)| *prompt)
+}
+
+/// Load a system prompt by path or by name
+/// A path to an existing file is read first, then a user prompt with the name
+/// in the config directory and last the built-in prompt with the name.
+/// # Arguments
+/// * `name` - The prompt name like `software-engineer` or a path to a prompt file
+/// # Returns
+/// * `Result<String>` - The system prompt
+pub async fn load_prompt(name: &str) -> Result<String> {
+    let path = Path::new(name);
+    if path.is_file() {
+        return tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read system prompt: {}", path.display()));
+    }
+
+    if let Some(path) = user_prompt_path(name) {
+        return tokio::fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read system prompt: {}", path.display()));
+    }
+
+    built_in_prompt(name).map(|p| p.to_string()).ok_or_else(|| {
+        let names = list_prompts()
+            .map(|prompts| prompts.into_iter().map(|p| p.name).collect::<Vec<_>>())
+            .unwrap_or_default();
+        anyhow!(
+            "System prompt not found: {}. Use a file path or one of: {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+/// List the built-in prompts and the user prompts in the config directory
+/// # Returns
+/// * `Result<Vec<PromptEntry>>` - The prompts sorted by name
+pub fn list_prompts() -> Result<Vec<PromptEntry>> {
+    let mut prompts: Vec<PromptEntry> = BUILT_IN_PROMPTS
+        .iter()
+        .map(|(name, _)| PromptEntry {
+            name: name.to_string(),
+            source: PromptSource::BuiltIn,
+        })
+        .collect();
+
+    let dir = user_prompts_dir()?;
+    if dir.is_dir() {
+        let entries = std::fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read directory {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("txt") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let source = PromptSource::User(path.clone());
+            match prompts.iter_mut().find(|p| p.name == name) {
+                Some(prompt) => prompt.source = source,
+                None => prompts.push(PromptEntry {
+                    name: name.to_string(),
+                    source,
+                }),
+            }
+        }
+    }
+
+    prompts.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(prompts)
+}
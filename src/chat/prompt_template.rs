@@ -1,87 +1,306 @@
 use crate::chat::chat_config::ChatMessage;
+use crate::chat::prompt_library;
+use crate::docsplitter::code_loader::get_language_name;
+use crate::lancevectordb::query::QueryRecord;
 use anyhow::Context;
 use anyhow::Result;
-use handlebars::Handlebars;
+use handlebars::{handlebars_helper, Handlebars};
+use log::debug;
 use serde::Serialize;
 
 /// Prompt struct
+/// `content` holds the chat history with the role of each message,
+/// `context` holds the retrieved content sent to the model as a separate system message.
 #[derive(Serialize, Debug)]
 pub(crate) struct Prompt {
     pub(crate) system_message: String,
-    pub content: Vec<Option<ChatMessage>>,
+    pub context: Option<String>,
+    pub content: Vec<ChatMessage>,
     pub prompt: String,
 }
 
 impl Prompt {
     pub(crate) async fn new(
-        path: &str,
-        contents: &Vec<Option<ChatMessage>>,
+        system_prompt: &str,
+        context: Option<&str>,
+        contents: &[ChatMessage],
         prompt: &str,
     ) -> Result<Prompt> {
-        let system_prompt = get_system_prompt(path)
+        let system_prompt = get_system_prompt(system_prompt)
             .await
             .context("Failed to get system prompt")?;
         let prompt = Prompt {
             system_message: system_prompt,
-            content: contents.clone(),
+            context: context.map(|c| c.to_string()),
+            content: contents.to_vec(),
             prompt: prompt.to_string(),
         };
         Ok(prompt)
     }
 }
 
-/// Get system prompt from file
+/// Get system prompt by built-in name, user prompt name or file path
 /// # Arguments
-/// * `prompt_path` - Path to the system prompt file
+/// * `prompt_name` - Name or path of the system prompt
 /// # Returns
 /// * `Result<String>` - System prompt
-async fn get_system_prompt(prompt_path: &str) -> Result<String> {
-    let path = std::path::Path::new(prompt_path);
-
-    if std::fs::metadata(path).is_err() || !std::fs::metadata(path).unwrap().is_file() {
-        anyhow::bail!("System prompt file not found: {}", path.display());
-    }
-
-    let system_prompt = tokio::fs::read_to_string(path)
+async fn get_system_prompt(prompt_name: &str) -> Result<String> {
+    let system_prompt = prompt_library::load_prompt(prompt_name)
         .await
         .context("Failed to read system prompt")?;
 
     Ok(system_prompt)
 }
 
-/// Get template from file
-/// # Arguments
-/// * `prompt` - Prompt struct
-/// * `template_file` - Path to the template file
-/// # Returns
-/// * `Result<String>` - Rendered template
-#[allow(dead_code)]
-pub fn get_template(prompt: &Prompt, template_file: &str) -> Result<String> {
-    let template = std::fs::read_to_string(template_file).expect("Failed to read template file");
-
-    let mut handlebars = Handlebars::new();
-    //
-    // let template = r#"
-    // <|system|> {{ system_prompt }}</s>
-    // <|content|> {{ content }}</s>
-    // <|user|> {{ prompt }}</s>
-    // "#;
-
-    handlebars
-        .register_template_string("tpl", template)
-        .context("Failed to register template")?;
-
-    let data = Prompt {
-        system_message: prompt.system_message.to_string(),
-        content: prompt.content.clone(),
-        prompt: prompt.prompt.to_string(),
-    };
-
-    // Render the template with the data
-    let rendered = handlebars
-        .render("tpl", &data)
-        .context("Failed to render template")?;
-    // debug!("Rendered Template {}", rendered);
-
-    Ok(rendered)
+const TEMPLATE_NAME: &str = "prompt";
+
+// {{truncate content 200}} keeps the first 200 characters of the text
+handlebars_helper!(truncate: |text: str, max_chars: u64| {
+    let max_chars = max_chars as usize;
+    if text.chars().count() > max_chars {
+        format!("{}...", text.chars().take(max_chars).collect::<String>())
+    } else {
+        text.to_string()
+    }
+});
+
+// {{code_fence content language}} wraps the text in a fenced code block tagged with the language
+handlebars_helper!(code_fence: |text: str, language: str| {
+    format!("```{}\n{}\n```", language, text)
+});
+
+/// A retrieved chunk as seen by the prompt template
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct TemplateChunk {
+    pub path: String,
+    pub chunk_number: Option<i32>,
+    pub score: Option<f32>,
+    pub language: String,
+    pub content: String,
+}
+
+impl From<&QueryRecord> for TemplateChunk {
+    fn from(record: &QueryRecord) -> Self {
+        TemplateChunk {
+            path: record.path().to_string(),
+            chunk_number: record.chunk_number,
+            score: record.score(),
+            language: get_language_name(record.path()).to_string(),
+            content: record.content.clone(),
+        }
+    }
+}
+
+/// Variables available in the prompt templates
+/// `content` and `prompt` are kept for the older templates in `template/*.hbs`
+#[derive(Serialize, Debug)]
+struct TemplateData<'a> {
+    system_message: &'a str,
+    content: &'a str,
+    prompt: &'a str,
+    question: &'a str,
+    chunks: &'a [TemplateChunk],
+    repository: &'a str,
+    language: &'a str,
+    date: String,
+}
+
+/// PromptTemplate renders the user message from a Handlebars template
+/// with the retrieved chunks, the question, repository name, language and date
+pub(crate) struct PromptTemplate {
+    handlebars: Handlebars<'static>,
+    repository: String,
+    chunks: Vec<TemplateChunk>,
+}
+
+impl PromptTemplate {
+    /// Load the template from file and validate it by rendering sample data
+    /// so errors like unknown variables are reported before running the query
+    /// # Arguments
+    /// * `template_file` - Path to the template file
+    /// # Returns
+    /// * `Result<PromptTemplate>` - The validated template
+    pub fn load(template_file: &str) -> Result<PromptTemplate> {
+        let template = std::fs::read_to_string(template_file)
+            .with_context(|| format!("Failed to read template file: {}", template_file))?;
+
+        let mut handlebars = Handlebars::new();
+        handlebars.set_strict_mode(true);
+        // prompts are not html, keep the code as is
+        handlebars.register_escape_fn(handlebars::no_escape);
+        handlebars.register_helper("truncate", Box::new(truncate));
+        handlebars.register_helper("code_fence", Box::new(code_fence));
+        handlebars
+            .register_template_string(TEMPLATE_NAME, template)
+            .with_context(|| format!("Failed to register template: {}", template_file))?;
+
+        let prompt_template = PromptTemplate {
+            handlebars,
+            repository: String::new(),
+            chunks: Vec::new(),
+        };
+
+        let sample = vec![TemplateChunk {
+            path: "main.rs".to_string(),
+            chunk_number: Some(0),
+            score: Some(1.0),
+            language: "rust".to_string(),
+            content: "fn main() {}".to_string(),
+        }];
+        prompt_template
+            .render_with_chunks("system", "question", "context", &sample)
+            .with_context(|| format!("Invalid template: {}", template_file))?;
+
+        Ok(prompt_template)
+    }
+
+    /// Set the name of the repository the chunks were loaded from
+    pub fn with_repository(mut self, repository: &str) -> Self {
+        self.repository = repository.to_string();
+        self
+    }
+
+    /// Set the chunks retrieved from the vector database
+    pub fn with_chunks(mut self, records: &[QueryRecord]) -> Self {
+        self.chunks = records.iter().map(TemplateChunk::from).collect();
+        self
+    }
+
+    /// Render the user message for the question
+    /// # Arguments
+    /// * `system_message` - The system prompt
+    /// * `question` - The user question
+    /// * `context` - The retrieved content joined as text
+    /// # Returns
+    /// * `Result<String>` - Rendered template
+    pub fn render(&self, system_message: &str, question: &str, context: &str) -> Result<String> {
+        self.render_with_chunks(system_message, question, context, &self.chunks)
+    }
+
+    fn render_with_chunks(
+        &self,
+        system_message: &str,
+        question: &str,
+        context: &str,
+        chunks: &[TemplateChunk],
+    ) -> Result<String> {
+        let data = TemplateData {
+            system_message,
+            content: context,
+            prompt: question,
+            question,
+            chunks,
+            repository: &self.repository,
+            language: main_language(chunks),
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+        };
+
+        let rendered = self
+            .handlebars
+            .render(TEMPLATE_NAME, &data)
+            .context("Failed to render template")?;
+        debug!("Rendered Template {}", rendered);
+
+        Ok(rendered)
+    }
+}
+
+/// The language most of the chunks are written in
+fn main_language(chunks: &[TemplateChunk]) -> &str {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for chunk in chunks.iter().filter(|c| !c.language.is_empty()) {
+        match counts.iter_mut().find(|(l, _)| *l == chunk.language) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((&chunk.language, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map_or("", |(language, _)| language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAG_TEMPLATE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/template/rag_template.hbs");
+
+    /// Write the template in its own temp path
+    fn template_file(name: &str, template: &str) -> Result<String> {
+        let path = std::env::temp_dir().join(format!(
+            "vector-embed-test-{}-{}.hbs",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, template)?;
+        Ok(path.display().to_string())
+    }
+
+    fn record(path: &str, chunk_number: i32, content: &str) -> QueryRecord {
+        QueryRecord {
+            id: Some(format!("{}:{}", path, chunk_number)),
+            content: content.to_string(),
+            metadata: path.rsplit('/').next().unwrap().to_string(),
+            chunk_number: Some(chunk_number),
+            distance: None,
+            similarity: Some(0.5),
+            file_path: Some(path.to_string()),
+            start_line: None,
+            end_line: None,
+            rerank_score: None,
+        }
+    }
+
+    #[test]
+    fn rag_template_renders_the_chunks() -> Result<()> {
+        let long = "x".repeat(4100);
+        let records = vec![
+            record("src/main.rs", 0, "fn main() {}"),
+            record("src/chat/mod.rs", 3, &long),
+            record("scripts/load.py", 1, "def load(): pass"),
+        ];
+        let template = PromptTemplate::load(RAG_TEMPLATE)?
+            .with_repository("vector-embed-rust")
+            .with_chunks(&records);
+
+        let rendered = template.render("system", "Where is main?", "context")?;
+        assert!(rendered.starts_with("Repository: vector-embed-rust (rust) "));
+        assert!(
+            rendered.contains("Source: src/main.rs chunk 0 score 0.5\n```rust\nfn main() {}\n```")
+        );
+        assert!(rendered.contains(&format!(
+            "Source: src/chat/mod.rs chunk 3 score 0.5\n```rust\n{}...\n```",
+            "x".repeat(4000)
+        )));
+        assert!(rendered.contains("```python\ndef load(): pass\n```"));
+        assert!(rendered.trim_end().ends_with("Question: Where is main?"));
+        Ok(())
+    }
+
+    #[test]
+    fn helpers_keep_the_text_as_is() -> Result<()> {
+        let path = template_file(
+            "helpers",
+            "{{truncate question 5}}|{{truncate question 50}}|{{code_fence content \"rust\"}}",
+        )?;
+        let rendered =
+            PromptTemplate::load(&path)?.render("system", "Is a < b && c?", "let x = &y;")?;
+        assert_eq!(
+            rendered,
+            "Is a ...|Is a < b && c?|```rust\nlet x = &y;\n```"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn strict_mode_rejects_unknown_variables() -> Result<()> {
+        let path = template_file("unknown", "{{question}} {{repo_name}}")?;
+        let error = PromptTemplate::load(&path).err().unwrap();
+        assert!(format!("{:#}", error).starts_with(&format!("Invalid template: {}", path)));
+
+        let path = template_file("unknown-chunk", "{{#each chunks}}{{file_name}}{{/each}}")?;
+        assert!(PromptTemplate::load(&path).is_err());
+        Ok(())
+    }
 }
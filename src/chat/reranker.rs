@@ -0,0 +1,428 @@
+use crate::app::constants::{RERANK_API, RERANK_CONCURRENCY};
+use crate::chat::chat_config::ChatRequest;
+use crate::chat::prompt_template::Prompt;
+use crate::chat::response_format::ResponseFormat;
+use crate::lancevectordb::query::QueryRecord;
+use crate::lancevectordb::HttpsClient;
+use anyhow::{anyhow, Context, Result};
+use futures::StreamExt;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::json;
+
+const SCORER_SYSTEM_PROMPT: &str =
+    "You rate how relevant a code chunk is to a question about a code repository. \
+Give a score from 0 to 10, 10 when the chunk answers the question and 0 when it is unrelated. \
+Answer only with JSON.";
+
+/// RerankMethod selects how the retrieved chunks are scored against the question
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RerankMethod {
+    /// A Cohere or Jina compatible `/v1/rerank` endpoint
+    Api,
+    /// The chat model scores every chunk on its own
+    Llm,
+}
+
+#[derive(Deserialize, Debug)]
+struct RerankResponse {
+    results: Vec<RerankResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RerankResult {
+    index: usize,
+    relevance_score: f32,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChunkScore {
+    score: f32,
+}
+
+/// Reranker scores the retrieved chunks against the question and keeps the best ones
+/// Rows of the same chunk are scored together and share the rerank score
+pub(crate) struct Reranker {
+    method: RerankMethod,
+    provider: String,
+    api_url: String,
+    api_key: String,
+    model: String,
+    candidates: usize,
+    top_k: usize,
+}
+
+impl Reranker {
+    pub fn new(
+        method: RerankMethod,
+        provider: &str,
+        api_url: &str,
+        api_key: &str,
+        model: &str,
+    ) -> Reranker {
+        Reranker {
+            method,
+            provider: provider.to_string(),
+            api_url: api_url.to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            candidates: usize::MAX,
+            top_k: usize::MAX,
+        }
+    }
+
+    /// Set the number of chunks from the vector search that are reranked
+    pub fn with_candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates.max(1);
+        self
+    }
+
+    /// Set the number of chunks kept after reranking
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k.max(1);
+        self
+    }
+
+    /// Rerank the retrieved rows
+    /// # Arguments
+    /// * `question` - The user question
+    /// * `records` - The retrieved rows in retrieval order
+    /// * `client` - The HTTP client to use for requests
+    /// # Returns
+    /// * `Result<Vec<QueryRecord>>` - The rows of the best chunks with the rerank score set, best first
+    pub async fn rerank(
+        &self,
+        question: &str,
+        records: &[QueryRecord],
+        client: &HttpsClient,
+    ) -> Result<Vec<QueryRecord>> {
+        let chunks = self.candidate_chunks(records);
+        if chunks.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let documents: Vec<String> = chunks.iter().map(|c| chunk_text(c)).collect();
+        let scores = match self.method {
+            RerankMethod::Api => self.score_with_api(question, &documents, client).await,
+            RerankMethod::Llm => Ok(self.score_with_llm(question, &documents, client).await),
+        }
+        .with_context(|| format!("Failed to rerank with {}", self.model))?;
+
+        Ok(self.keep_best(chunks, scores))
+    }
+
+    /// Group the rows by chunk and keep the nearest chunks as the candidates
+    fn candidate_chunks(&self, records: &[QueryRecord]) -> Vec<Vec<QueryRecord>> {
+        let mut chunks = group_by_chunk(records);
+        chunks.sort_by(|a, b| {
+            let a = chunk_distance(a).unwrap_or(f32::MAX);
+            let b = chunk_distance(b).unwrap_or(f32::MAX);
+            a.total_cmp(&b)
+        });
+        chunks.truncate(self.candidates);
+        chunks
+    }
+
+    /// Set the scores on the rows of the chunks and keep the rows of the top_k best chunks
+    fn keep_best(&self, chunks: Vec<Vec<QueryRecord>>, scores: Vec<f32>) -> Vec<QueryRecord> {
+        let mut scored: Vec<(f32, Vec<QueryRecord>)> = chunks
+            .into_iter()
+            .zip(scores)
+            .map(|(mut chunk, score)| {
+                for record in chunk.iter_mut() {
+                    record.rerank_score = Some(score);
+                }
+                (score, chunk)
+            })
+            .collect();
+        // stable sort keeps the vector search order for equal scores
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        for (score, chunk) in &scored {
+            debug!(
+                "Rerank score {:.3} for {} chunk {:?}",
+                score,
+                chunk[0].path(),
+                chunk[0].chunk_number
+            );
+        }
+        info!(
+            "Reranked {} chunks with {:?}, keeping {}",
+            scored.len(),
+            self.method,
+            self.top_k.min(scored.len())
+        );
+
+        scored
+            .into_iter()
+            .take(self.top_k)
+            .flat_map(|(_, chunk)| chunk)
+            .collect()
+    }
+
+    /// Score the documents with a `/v1/rerank` endpoint
+    async fn score_with_api(
+        &self,
+        question: &str,
+        documents: &[String],
+        client: &HttpsClient,
+    ) -> Result<Vec<f32>> {
+        let rerank_url = format!("{}/{}", self.api_url.trim_end_matches('/'), RERANK_API);
+        let body = json!({
+            "model": self.model,
+            "query": question,
+            "documents": documents,
+            "top_n": documents.len(),
+        });
+
+        let request = http::Request::builder()
+            .method("POST")
+            .uri(&rerank_url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .body(Full::new(Bytes::from(body.to_string())))
+            .context("Failed to build request")?;
+
+        let response = client
+            .request(request)
+            .await
+            .with_context(|| format!("Failed to send request to {}", &rerank_url))?;
+        let status = response.status();
+        let body_bytes = http_body_util::BodyExt::collect(response.into_body())
+            .await?
+            .to_bytes();
+        let body = std::str::from_utf8(&body_bytes)?;
+        if !status.is_success() {
+            return Err(anyhow!("Rerank request failed with {}: {}", status, body));
+        }
+
+        api_scores(body, documents.len())
+    }
+
+    /// Score every document with the chat model, the score between 0 and 10 is scaled to 0 to 1
+    /// A document that fails to score gets the lowest score so it keeps its retrieval order
+    /// after the scored documents
+    async fn score_with_llm(
+        &self,
+        question: &str,
+        documents: &[String],
+        client: &HttpsClient,
+    ) -> Vec<f32> {
+        futures::stream::iter(documents.iter().enumerate())
+            .map(|(i, document)| async move {
+                self.score_document(question, document, client)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!("Failed to score chunk {}, ranking it last: {:#}", i, e);
+                        0.0
+                    })
+            })
+            .buffered(RERANK_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    async fn score_document(
+        &self,
+        question: &str,
+        document: &str,
+        client: &HttpsClient,
+    ) -> Result<f32> {
+        let prompt = Prompt {
+            system_message: SCORER_SYSTEM_PROMPT.to_string(),
+            context: None,
+            content: Vec::new(),
+            prompt: format!("Question: {}\n\nCode chunk:\n{}", question, document),
+        };
+
+        let format = ResponseFormat::JsonSchema(json!({
+            "type": "object",
+            "properties": {
+                "score": { "type": "number" }
+            },
+            "required": ["score"]
+        }));
+
+        let chat_request = ChatRequest::new(
+            &self.provider,
+            &self.model,
+            self.api_url.clone(),
+            self.api_key.clone(),
+            false,
+            Some(format.clone()),
+            None,
+            prompt,
+        );
+
+        let response = super::send_chat(chat_request, &format, None, client).await?;
+        let content = response
+            .get_message()
+            .map(|m| m.get_content().as_str())
+            .unwrap_or_default();
+        let score: ChunkScore =
+            serde_json::from_str(content).context("Failed to parse chunk score")?;
+
+        Ok(score.score.clamp(0.0, 10.0) / 10.0)
+    }
+}
+
+/// The scores of a `/v1/rerank` response in document order, documents missing from the results score 0
+fn api_scores(body: &str, documents: usize) -> Result<Vec<f32>> {
+    let response: RerankResponse =
+        serde_json::from_str(body).context("Failed to parse rerank response")?;
+    let mut scores = vec![0.0; documents];
+    for result in response.results {
+        if let Some(score) = scores.get_mut(result.index) {
+            *score = result.relevance_score;
+        }
+    }
+    Ok(scores)
+}
+
+/// Group the rows of the same chunk of a file keeping the retrieval order
+fn group_by_chunk(records: &[QueryRecord]) -> Vec<Vec<QueryRecord>> {
+    let mut chunks: Vec<Vec<QueryRecord>> = Vec::new();
+    for record in records {
+        let existing = record.chunk_number.and_then(|chunk_number| {
+            chunks
+                .iter_mut()
+                .find(|c| c[0].chunk_number == Some(chunk_number) && c[0].path() == record.path())
+        });
+        match existing {
+            Some(chunk) => chunk.push(record.clone()),
+            None => chunks.push(vec![record.clone()]),
+        }
+    }
+    chunks
+}
+
+fn chunk_distance(chunk: &[QueryRecord]) -> Option<f32> {
+    chunk
+        .iter()
+        .filter_map(|r| r.distance)
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+fn chunk_text(chunk: &[QueryRecord]) -> String {
+    let mut lines: Vec<&QueryRecord> = chunk.iter().collect();
+    lines.sort_by_key(|r| r.start_line);
+    let content = lines
+        .iter()
+        .map(|r| r.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("File: {}\n{}", chunk[0].path(), content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(path: &str, chunk_number: i32, line: i32, distance: f32) -> QueryRecord {
+        QueryRecord {
+            id: Some(format!("{}:{}", path, line)),
+            content: format!("{} line {}", path, line),
+            metadata: path.rsplit('/').next().unwrap().to_string(),
+            chunk_number: Some(chunk_number),
+            distance: Some(distance),
+            similarity: None,
+            file_path: Some(path.to_string()),
+            start_line: Some(line),
+            end_line: Some(line),
+            rerank_score: None,
+        }
+    }
+
+    /// Rows of four chunks, chunk n of src/a.rs is at distance n and has two rows
+    fn records() -> Vec<QueryRecord> {
+        vec![
+            record("src/a.rs", 2, 11, 2.0),
+            record("src/a.rs", 0, 1, 0.0),
+            record("src/b.rs", 0, 1, 1.5),
+            record("src/a.rs", 1, 6, 1.0),
+            record("src/a.rs", 0, 2, 0.5),
+        ]
+    }
+
+    fn reranker() -> Reranker {
+        Reranker::new(
+            RerankMethod::Api,
+            "openai",
+            "http://localhost",
+            "",
+            "rerank",
+        )
+    }
+
+    fn chunk_ids(records: &[QueryRecord]) -> Vec<(String, i32)> {
+        let mut ids: Vec<(String, i32)> = Vec::new();
+        for record in records {
+            let id = (record.path().to_string(), record.chunk_number.unwrap());
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    #[test]
+    fn api_scores_are_in_document_order() -> Result<()> {
+        let body = r#"{"results": [
+            {"index": 2, "relevance_score": 0.9},
+            {"index": 0, "relevance_score": 0.4},
+            {"index": 7, "relevance_score": 1.0}
+        ]}"#;
+        assert_eq!(api_scores(body, 3)?, vec![0.4, 0.0, 0.9]);
+        assert!(api_scores(r#"{"data": []}"#, 3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn candidates_are_the_nearest_chunks() {
+        let chunks = reranker().with_candidates(2).candidate_chunks(&records());
+        assert_eq!(chunks.len(), 2);
+        // both rows of the nearest chunk are one candidate
+        assert_eq!(chunks[0].len(), 2);
+        assert_eq!(chunks[0][0].path(), "src/a.rs");
+        assert_eq!(chunks[0][0].chunk_number, Some(0));
+        assert_eq!(chunks[1][0].chunk_number, Some(1));
+
+        let chunks = reranker().candidate_chunks(&records());
+        assert_eq!(chunks.len(), 4);
+    }
+
+    #[test]
+    fn top_k_keeps_the_rows_of_the_best_chunks() {
+        let reranker = reranker().with_top_k(2);
+        let chunks = reranker.candidate_chunks(&records());
+        let kept = reranker.keep_best(chunks, vec![0.1, 0.8, 0.3, 0.1]);
+
+        assert_eq!(
+            chunk_ids(&kept),
+            vec![("src/a.rs".to_string(), 1), ("src/b.rs".to_string(), 0)]
+        );
+        assert_eq!(kept[0].rerank_score, Some(0.8));
+        assert_eq!(kept[1].rerank_score, Some(0.3));
+    }
+
+    #[test]
+    fn equal_scores_keep_the_retrieval_order() {
+        let reranker = reranker();
+        let chunks = reranker.candidate_chunks(&records());
+        // a chunk that failed to score has the lowest score
+        let kept = reranker.keep_best(chunks, vec![0.0, 0.5, 0.0, 0.0]);
+
+        assert_eq!(
+            chunk_ids(&kept),
+            vec![
+                ("src/a.rs".to_string(), 1),
+                ("src/a.rs".to_string(), 0),
+                ("src/b.rs".to_string(), 0),
+                ("src/a.rs".to_string(), 2),
+            ]
+        );
+        assert_eq!(kept.len(), 5);
+    }
+}
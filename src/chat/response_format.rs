@@ -0,0 +1,500 @@
+use crate::chat::chat_config::LLMProvider;
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+
+/// ResponseFormat is the format the model is asked to answer in
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseFormat {
+    /// Plain text, rendered as Markdown in the terminal
+    Text,
+    /// Any valid JSON
+    Json,
+    /// JSON matching the schema
+    JsonSchema(Value),
+}
+
+impl ResponseFormat {
+    /// Parse the `--format` flag, `text`, `json` or the path to a JSON schema file
+    /// # Arguments
+    /// * `format` - The format name or schema file path
+    /// # Returns
+    /// * `Result<ResponseFormat>` - The response format
+    pub fn parse(format: &str) -> Result<ResponseFormat> {
+        match format.to_lowercase().as_str() {
+            "text" => Ok(ResponseFormat::Text),
+            "json" => Ok(ResponseFormat::Json),
+            _ => {
+                let schema = std::fs::read_to_string(format)
+                    .with_context(|| format!("Failed to read JSON schema file: {}", format))?;
+                let schema: Value = serde_json::from_str(&schema)
+                    .with_context(|| format!("Failed to parse JSON schema: {}", format))?;
+                if !schema.is_object() {
+                    return Err(anyhow!("JSON schema must be an object: {}", format));
+                }
+                check_schema(&schema, "$")
+                    .with_context(|| format!("Unsupported JSON schema: {}", format))?;
+                Ok(ResponseFormat::JsonSchema(schema))
+            }
+        }
+    }
+
+    /// The `format` field of an Ollama chat request
+    pub fn ollama_format(&self) -> Option<Value> {
+        match self {
+            ResponseFormat::Text => None,
+            ResponseFormat::Json => Some(Value::String("json".to_string())),
+            ResponseFormat::JsonSchema(schema) => Some(schema.clone()),
+        }
+    }
+
+    /// The `response_format` field of an OpenAI chat request
+    pub fn openai_response_format(&self) -> Option<Value> {
+        match self {
+            ResponseFormat::Text => None,
+            ResponseFormat::Json => Some(json!({ "type": "json_object" })),
+            ResponseFormat::JsonSchema(schema) => Some(json!({
+                "type": "json_schema",
+                "json_schema": { "name": "response", "schema": schema },
+            })),
+        }
+    }
+
+    /// Get the Ollama `format` and the OpenAI `response_format` fields for the provider
+    pub fn request_field(&self, provider: &LLMProvider) -> (Option<Value>, Option<Value>) {
+        match provider {
+            LLMProvider::Ollama => (self.ollama_format(), None),
+            LLMProvider::OpenAI => (None, self.openai_response_format()),
+        }
+    }
+
+    pub fn is_json(&self) -> bool {
+        !matches!(self, ResponseFormat::Text)
+    }
+
+    /// Check the response content against the format
+    /// # Arguments
+    /// * `content` - The response content
+    /// # Returns
+    /// * `Vec<String>` - The violations, empty if the response is valid
+    pub fn validate(&self, content: &str) -> Vec<String> {
+        let schema = match self {
+            ResponseFormat::Text => return Vec::new(),
+            ResponseFormat::Json => None,
+            ResponseFormat::JsonSchema(schema) => Some(schema),
+        };
+
+        let value: Value = match serde_json::from_str(content) {
+            Ok(value) => value,
+            Err(e) => return vec![format!("response is not valid JSON: {}", e)],
+        };
+
+        let mut errors = Vec::new();
+        if let Some(schema) = schema {
+            validate_schema(schema, &value, "$", &mut errors);
+        }
+        errors
+    }
+}
+
+/// The JSON schema keywords the responses are validated against
+const SCHEMA_KEYWORDS: [&str; 6] = [
+    "type",
+    "enum",
+    "required",
+    "properties",
+    "additionalProperties",
+    "items",
+];
+/// Keywords that only describe the schema and do not constrain the value
+const SCHEMA_ANNOTATIONS: [&str; 6] = [
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "examples",
+];
+const SCHEMA_TYPES: [&str; 7] = [
+    "object", "array", "string", "number", "integer", "boolean", "null",
+];
+
+/// Check the schema only uses the keywords the responses are validated against
+/// A keyword like `minimum`, `pattern`, `oneOf` or `$ref` would otherwise be ignored and accept any value
+/// # Arguments
+/// * `schema` - The schema or subschema
+/// * `path` - The location of the subschema like `$.properties.name`
+/// # Returns
+/// * `Result<()>` - An error naming the first unsupported keyword
+fn check_schema(schema: &Value, path: &str) -> Result<()> {
+    let object = schema
+        .as_object()
+        .ok_or_else(|| anyhow!("{} should be a schema object", path))?;
+
+    for (keyword, value) in object {
+        let keyword_path = format!("{}.{}", path, keyword);
+        match keyword.as_str() {
+            "type" => {
+                let types = match value {
+                    Value::String(t) => vec![Some(t.as_str())],
+                    Value::Array(ts) => ts.iter().map(|t| t.as_str()).collect(),
+                    _ => vec![None],
+                };
+                if let Some(t) = types
+                    .iter()
+                    .find(|t| !t.is_some_and(|t| SCHEMA_TYPES.contains(&t)))
+                {
+                    return Err(anyhow!(
+                        "{} has the unsupported type {}, use one of {}",
+                        keyword_path,
+                        t.unwrap_or("that is not a string"),
+                        SCHEMA_TYPES.join(", ")
+                    ));
+                }
+            }
+            // a value is compared as a whole, arrays and objects in an enum are not matched by their items
+            "enum" => match value {
+                Value::Array(allowed) if !allowed.iter().any(|v| v.is_array() || v.is_object()) => {
+                }
+                Value::Array(_) => {
+                    return Err(anyhow!(
+                        "{} should only list strings, numbers, booleans or null",
+                        keyword_path
+                    ))
+                }
+                _ => return Err(anyhow!("{} should be an array", keyword_path)),
+            },
+            "required" => {
+                if !value
+                    .as_array()
+                    .is_some_and(|keys| keys.iter().all(|k| k.is_string()))
+                {
+                    return Err(anyhow!("{} should be an array of strings", keyword_path));
+                }
+            }
+            "properties" => {
+                let properties = value
+                    .as_object()
+                    .ok_or_else(|| anyhow!("{} should be an object", keyword_path))?;
+                for (key, property) in properties {
+                    check_schema(property, &format!("{}.{}", keyword_path, key))?;
+                }
+            }
+            // only unknown properties can be refused, schemas for them are not supported
+            "additionalProperties" => {
+                if !value.is_boolean() {
+                    return Err(anyhow!("{} should be true or false", keyword_path));
+                }
+            }
+            "items" => check_schema(value, &keyword_path)?,
+            keyword if SCHEMA_ANNOTATIONS.contains(&keyword) => {}
+            _ => {
+                return Err(anyhow!(
+                    "{} is not supported, the supported keywords are {}",
+                    keyword_path,
+                    SCHEMA_KEYWORDS.join(", ")
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Validate the value against the supported JSON schema keywords
+/// `type`, `enum`, `required`, `properties`, `additionalProperties: false` and `items`
+fn validate_schema(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| is_type(value, t)) {
+            errors.push(format!(
+                "{} should be of type {} but is {}",
+                path,
+                types.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            errors.push(format!(
+                "{} should be one of {}",
+                path,
+                Value::from(allowed.clone())
+            ));
+        }
+    }
+
+    if let Value::Object(object) = value {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !object.contains_key(key) {
+                    errors.push(format!("{} is missing the required property {}", path, key));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (key, item) in object {
+            match properties.and_then(|p| p.get(key)) {
+                Some(property) => {
+                    validate_schema(property, item, &format!("{}.{}", path, key), errors)
+                }
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    errors.push(format!("{} has the unknown property {}", path, key))
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let (Value::Array(values), Some(items)) = (value, schema.get("items")) {
+        for (i, item) in values.iter().enumerate() {
+            validate_schema(items, item, &format!("{}[{}]", path, i), errors);
+        }
+    }
+}
+
+fn is_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+const BOLD: &str = "\x1b[1m";
+const ITALIC: &str = "\x1b[3m";
+const CYAN: &str = "\x1b[36m";
+const YELLOW: &str = "\x1b[33m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Render a text response for the output, Markdown is styled only for a terminal
+/// so piped output stays plain
+/// # Arguments
+/// * `content` - The response content
+/// * `is_terminal` - If the output is a terminal
+/// # Returns
+/// * `String` - The text to print
+pub fn render_text(content: &str, is_terminal: bool) -> String {
+    if is_terminal {
+        render_markdown(content)
+    } else {
+        content.to_string()
+    }
+}
+
+/// Render Markdown for the terminal with ANSI styles
+/// Headings and bold text are bold, inline code and code blocks are colored
+/// and list bullets are replaced, everything else is printed as is.
+fn render_markdown(markdown: &str) -> String {
+    let mut rendered = String::with_capacity(markdown.len());
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+            let language = trimmed.trim_start_matches('`').trim();
+            if in_code_block && !language.is_empty() {
+                rendered.push_str(&format!("{}{}{}\n", DIM, language, RESET));
+            }
+            continue;
+        }
+
+        if in_code_block {
+            rendered.push_str(&format!("{}    {}{}\n", YELLOW, line, RESET));
+            continue;
+        }
+
+        let heading = trimmed.trim_start_matches('#');
+        if trimmed.starts_with('#') && heading.starts_with(' ') {
+            rendered.push_str(&format!("{}{}{}\n", BOLD, heading.trim(), RESET));
+            continue;
+        }
+
+        let indent = &line[..line.len() - trimmed.len()];
+        if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            rendered.push_str(&format!("{}  • {}\n", indent, render_inline(item)));
+            continue;
+        }
+
+        rendered.push_str(&render_inline(line));
+        rendered.push('\n');
+    }
+
+    rendered
+}
+
+/// Render the inline `code`, **bold** and *italic* spans of a line
+fn render_inline(line: &str) -> String {
+    let mut rendered = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while !rest.is_empty() {
+        let (marker, style) = if rest.starts_with('`') {
+            ("`", CYAN)
+        } else if rest.starts_with("**") {
+            ("**", BOLD)
+        } else if rest.starts_with('*') && !rest.starts_with("* ") {
+            ("*", ITALIC)
+        } else {
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let next = rest[first..]
+                .find(['`', '*'])
+                .map_or(rest.len(), |i| i + first);
+            rendered.push_str(&rest[..next]);
+            rest = &rest[next..];
+            continue;
+        };
+
+        match rest[marker.len()..].find(marker) {
+            Some(end) if end > 0 => {
+                let span = &rest[marker.len()..marker.len() + end];
+                rendered.push_str(&format!("{}{}{}", style, span, RESET));
+                rest = &rest[2 * marker.len() + end..];
+            }
+            _ => {
+                rendered.push_str(marker);
+                rest = &rest[marker.len()..];
+            }
+        }
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema_error(schema: Value) -> String {
+        format!("{:#}", check_schema(&schema, "$").unwrap_err())
+    }
+
+    fn violations(schema: Value, value: Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        validate_schema(&schema, &value, "$", &mut errors);
+        errors
+    }
+
+    #[test]
+    fn unsupported_schema_keywords_are_refused() {
+        assert_eq!(
+            schema_error(json!({ "type": "object", "oneOf": [] })),
+            "$.oneOf is not supported, the supported keywords are \
+             type, enum, required, properties, additionalProperties, items"
+        );
+        let nested = json!({
+            "type": "object",
+            "properties": { "age": { "type": "integer", "minimum": 0 } }
+        });
+        assert!(schema_error(nested).starts_with("$.properties.age.minimum is not supported"));
+        assert!(schema_error(json!({ "items": { "$ref": "#/item" } }))
+            .starts_with("$.items.$ref is not supported"));
+        assert!(schema_error(json!({ "type": "date" }))
+            .starts_with("$.type has the unsupported type date"));
+        assert!(schema_error(json!({ "additionalProperties": {} }))
+            .starts_with("$.additionalProperties should be true or false"));
+
+        let supported = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "Answer",
+            "type": "object",
+            "properties": {
+                "answer": { "type": "string", "description": "The answer" },
+                "files": { "type": "array", "items": { "type": "string" } },
+                "confidence": { "enum": ["low", "high"] }
+            },
+            "required": ["answer"],
+            "additionalProperties": false
+        });
+        assert!(check_schema(&supported, "$").is_ok());
+    }
+
+    #[test]
+    fn schema_violations_are_found() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "answer": { "type": "string" },
+                "files": { "type": "array", "items": { "type": "string" } },
+                "confidence": { "enum": ["low", "high"] }
+            },
+            "required": ["answer"],
+            "additionalProperties": false
+        });
+
+        assert!(violations(
+            schema.clone(),
+            json!({ "answer": "yes", "files": ["a.rs"] })
+        )
+        .is_empty());
+        assert_eq!(
+            violations(
+                schema.clone(),
+                json!({ "files": ["a.rs", 2], "confidence": "medium", "extra": true })
+            ),
+            vec![
+                "$ is missing the required property answer",
+                "$.confidence should be one of [\"low\",\"high\"]",
+                "$ has the unknown property extra",
+                "$.files[1] should be of type string but is number",
+            ]
+        );
+        assert_eq!(
+            violations(schema, json!([])),
+            vec!["$ should be of type object but is array"]
+        );
+    }
+
+    #[test]
+    fn responses_are_validated_against_the_format() {
+        let format = ResponseFormat::JsonSchema(json!({ "type": "integer" }));
+        assert!(format.validate("42").is_empty());
+        assert_eq!(
+            format.validate("4.2"),
+            vec!["$ should be of type integer but is number"]
+        );
+        assert!(
+            ResponseFormat::Json.validate("not json")[0].starts_with("response is not valid JSON")
+        );
+        assert!(ResponseFormat::Text.validate("not json").is_empty());
+    }
+
+    #[test]
+    fn markdown_is_rendered_only_for_a_terminal() {
+        let content = "# Answer\nUse `cargo run` with **care**";
+        assert_eq!(render_text(content, false), content);
+
+        let rendered = render_text(content, true);
+        assert_eq!(
+            rendered,
+            format!("{BOLD}Answer{RESET}\nUse {CYAN}cargo run{RESET} with {BOLD}care{RESET}\n")
+        );
+    }
+}
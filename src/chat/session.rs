@@ -0,0 +1,380 @@
+use crate::app::config::app_data_dir;
+use crate::app::constants::{MAX_SESSION_ID_ATTEMPTS, SESSIONS_DIR};
+use crate::chat::chat_config::ChatResponse;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// SessionTurn is one question and answer of a chat session, stored as one JSON line
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionTurn {
+    pub session_id: String,
+    pub turn: usize,
+    pub created_at: String,
+    pub provider: String,
+    pub model: String,
+    pub question: String,
+    pub answer: String,
+    pub sources: Vec<String>,
+    pub total_duration: Option<i64>,
+    pub load_duration: Option<i64>,
+    pub prompt_eval_count: Option<i32>,
+    pub prompt_eval_duration: Option<i64>,
+    pub eval_count: Option<i32>,
+    pub eval_duration: Option<i64>,
+}
+
+/// SessionSummary is the overview of a saved session shown by `sessions list`
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub id: String,
+    pub turns: usize,
+    pub model: String,
+    pub updated_at: String,
+    pub first_question: String,
+}
+
+/// ChatSession appends the turns of a chat to `<data dir>/sessions/<id>.jsonl`
+#[derive(Debug)]
+pub struct ChatSession {
+    id: String,
+    path: PathBuf,
+    turns: usize,
+}
+
+impl ChatSession {
+    /// Start a new session, the id is the local start time
+    /// The session file is created right away, a chat started in the same second gets a numbered id
+    pub fn new() -> Result<ChatSession> {
+        ChatSession::new_in(&sessions_dir()?)
+    }
+
+    /// Start a new session in the directory
+    fn new_in(dir: &Path) -> Result<ChatSession> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+
+        let started = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        for attempt in 1..=MAX_SESSION_ID_ATTEMPTS {
+            let id = match attempt {
+                1 => started.clone(),
+                n => format!("{}-{}", started, n),
+            };
+            let path = dir.join(session_file_name(&id)?);
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(ChatSession { id, path, turns: 0 }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to create session file {}", path.display())
+                    })
+                }
+            }
+        }
+        Err(anyhow!(
+            "Failed to find a free session id for {} after {} attempts",
+            started,
+            MAX_SESSION_ID_ATTEMPTS
+        ))
+    }
+
+    /// Open an existing session to append more turns
+    pub async fn open(id: &str) -> Result<(ChatSession, Vec<SessionTurn>)> {
+        let turns = load_session(id).await?;
+        let session = ChatSession {
+            id: id.to_string(),
+            path: session_path(id)?,
+            turns: turns.len(),
+        };
+        Ok((session, turns))
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    /// Create the next turn of the session from the chat response
+    pub fn new_turn(
+        &self,
+        provider: &str,
+        question: &str,
+        sources: &[String],
+        response: &ChatResponse,
+    ) -> SessionTurn {
+        SessionTurn {
+            session_id: self.id.clone(),
+            turn: self.turns + 1,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            provider: provider.to_string(),
+            model: response.get_model().to_string(),
+            question: question.to_string(),
+            answer: response
+                .get_message()
+                .map(|m| m.get_content().to_string())
+                .unwrap_or_default(),
+            sources: sources.to_vec(),
+            total_duration: response.get_total_duration(),
+            load_duration: response.get_load_duration(),
+            prompt_eval_count: response.get_prompt_eval_count(),
+            prompt_eval_duration: response.get_prompt_eval_duration(),
+            eval_count: response.get_eval_count(),
+            eval_duration: response.get_eval_duration(),
+        }
+    }
+
+    /// Append the turn as a JSON line to the session file
+    pub async fn append(&mut self, turn: &SessionTurn) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+
+        let mut line = serde_json::to_string(turn).context("Failed to serialize session turn")?;
+        line.push('\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open session file {}", self.path.display()))?;
+        file.write_all(line.as_bytes())
+            .await
+            .context("Failed to write session turn")?;
+
+        self.turns += 1;
+        debug!("Saved turn {} of session {}", self.turns, self.id);
+        Ok(())
+    }
+}
+
+/// Get the directory the sessions are stored in
+fn sessions_dir() -> Result<PathBuf> {
+    Ok(app_data_dir()?.join(SESSIONS_DIR))
+}
+
+fn session_path(id: &str) -> Result<PathBuf> {
+    let file_name = session_file_name(id)?;
+    Ok(sessions_dir()?.join(file_name))
+}
+
+/// The file name of the session, an id that could leave the sessions directory is an error
+fn session_file_name(id: &str) -> Result<String> {
+    if id.is_empty() || id.contains(['/', '\\']) || id.contains("..") {
+        return Err(anyhow!("Invalid session id: {}", id));
+    }
+    Ok(format!("{}.jsonl", id))
+}
+
+/// Load all the turns of a session
+/// # Arguments
+/// * `id` - The session id
+/// # Returns
+/// * `Result<Vec<SessionTurn>>` - The turns in the order they were saved
+pub async fn load_session(id: &str) -> Result<Vec<SessionTurn>> {
+    read_turns(&session_path(id)?, id).await
+}
+
+async fn read_turns(path: &Path, id: &str) -> Result<Vec<SessionTurn>> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Session not found: {}", id))?;
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).context("Failed to parse session turn"))
+        .collect()
+}
+
+/// List the saved sessions, the most recent first
+/// # Returns
+/// * `Result<Vec<SessionSummary>>` - The session summaries
+pub async fn list_sessions() -> Result<Vec<SessionSummary>> {
+    list_sessions_in(&sessions_dir()?).await
+}
+
+async fn list_sessions_in(dir: &Path) -> Result<Vec<SessionSummary>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut sessions = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .with_context(|| format!("Failed to read directory {}", dir.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+
+        // a corrupt session does not hide the others
+        let turns = match read_turns(&path, id).await {
+            Ok(turns) => turns,
+            Err(e) => {
+                warn!("Skipping session {}: {:#}", id, e);
+                continue;
+            }
+        };
+        let (Some(first), Some(last)) = (turns.first(), turns.last()) else {
+            continue;
+        };
+        sessions.push(SessionSummary {
+            id: id.to_string(),
+            turns: turns.len(),
+            model: last.model.clone(),
+            updated_at: last.created_at.clone(),
+            first_question: first.question.clone(),
+        });
+    }
+
+    sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(sessions)
+}
+
+/// Delete a saved session
+pub async fn delete_session(id: &str) -> Result<()> {
+    let path = session_path(id)?;
+    tokio::fs::remove_file(&path)
+        .await
+        .with_context(|| format!("Failed to delete session {}", id))?;
+    Ok(())
+}
+
+/// Export a session to Markdown to share it for example in a code review
+/// # Arguments
+/// * `id` - The session id
+/// # Returns
+/// * `Result<String>` - The Markdown document
+pub async fn export_markdown(id: &str) -> Result<String> {
+    let turns = load_session(id).await?;
+    let first = turns
+        .first()
+        .ok_or_else(|| anyhow!("Session {} has no turns", id))?;
+
+    let mut markdown = format!("# Chat session {}\n\n", id);
+    markdown.push_str(&format!(
+        "- Model: `{}` ({})\n- Started: {}\n- Turns: {}\n",
+        first.model,
+        first.provider,
+        first.created_at,
+        turns.len()
+    ));
+
+    for turn in &turns {
+        markdown.push_str(&format!("\n## Turn {}\n\n", turn.turn));
+        markdown.push_str(&format!("**Question**\n\n{}\n\n", turn.question));
+        markdown.push_str(&format!("**Answer**\n\n{}\n", turn.answer));
+
+        if !turn.sources.is_empty() {
+            let sources = turn
+                .sources
+                .iter()
+                .map(|s| format!("`{}`", s))
+                .collect::<Vec<_>>()
+                .join(", ");
+            markdown.push_str(&format!("\n**Sources:** {}\n", sources));
+        }
+
+        if let (Some(count), Some(duration)) = (turn.eval_count, turn.eval_duration) {
+            markdown.push_str(&format!(
+                "\n_{} tokens generated in {:.2}s by `{}`_\n",
+                count,
+                duration as f64 / 1e9,
+                turn.model
+            ));
+        }
+    }
+
+    Ok(markdown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty sessions directory of the test
+    fn temp_sessions_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("vector-embed-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn turn(session_id: &str, question: &str) -> SessionTurn {
+        SessionTurn {
+            session_id: session_id.to_string(),
+            turn: 1,
+            created_at: "2024-06-01T10:00:00+00:00".to_string(),
+            provider: "ollama".to_string(),
+            model: "qwen2:7b".to_string(),
+            question: question.to_string(),
+            answer: "An answer".to_string(),
+            sources: Vec::new(),
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_count: None,
+            prompt_eval_duration: None,
+            eval_count: None,
+            eval_duration: None,
+        }
+    }
+
+    #[test]
+    fn sessions_created_back_to_back_have_distinct_ids() -> Result<()> {
+        let dir = temp_sessions_dir("sessions-ids");
+        let first = ChatSession::new_in(&dir)?;
+        let second = ChatSession::new_in(&dir)?;
+        let third = ChatSession::new_in(&dir)?;
+
+        assert_ne!(first.get_id(), second.get_id());
+        assert_ne!(second.get_id(), third.get_id());
+        assert_ne!(first.get_id(), third.get_id());
+        assert!(second.path.is_file());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn corrupt_sessions_are_skipped() -> Result<()> {
+        let dir = temp_sessions_dir("sessions-corrupt");
+        let mut session = ChatSession::new_in(&dir)?;
+        let id = session.get_id().to_string();
+        session
+            .append(&turn(&id, "What does the loader do?"))
+            .await?;
+        std::fs::write(
+            dir.join("corrupt.jsonl"),
+            "{\"session_id\": \"corrupt\", \"turn\":",
+        )?;
+        std::fs::write(dir.join("notes.txt"), "not a session")?;
+
+        let sessions = list_sessions_in(&dir).await?;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, id);
+        assert_eq!(sessions[0].turns, 1);
+        assert_eq!(sessions[0].first_question, "What does the loader do?");
+        Ok(())
+    }
+
+    #[test]
+    fn session_ids_stay_in_the_sessions_directory() {
+        for id in ["../x", "..", "a/b", "a\\b", ""] {
+            assert!(session_path(id).is_err(), "{}", id);
+        }
+        assert_eq!(
+            session_file_name("20240601-100000-2").unwrap(),
+            "20240601-100000-2.jsonl"
+        );
+    }
+}
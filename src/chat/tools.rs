@@ -0,0 +1,351 @@
+use crate::chat::chat_config::ToolCall;
+use crate::lancevectordb::query::{self, QueryParams, QueryRecord};
+use crate::lancevectordb::HttpsClient;
+use anyhow::{anyhow, Result};
+use lancedb::Connection;
+use log::{debug, info};
+use serde_json::{json, Value};
+
+const SEARCH_CODE: &str = "search_code";
+const READ_FILE: &str = "read_file";
+const LIST_FILES: &str = "list_files";
+const DEFAULT_SEARCH_LIMIT: usize = 5;
+
+/// ToolContext holds what the built-in tools need to search the indexed repository
+pub(crate) struct ToolContext {
+    db: Connection,
+    table: String,
+    provider: String,
+    api_url: String,
+    api_key: String,
+    embed_model: String,
+    client: HttpsClient,
+    max_rounds: usize,
+    query_params: QueryParams,
+}
+
+impl ToolContext {
+    pub fn new(
+        db: Connection,
+        table: &str,
+        provider: &str,
+        api_url: &str,
+        api_key: &str,
+        embed_model: &str,
+        client: &HttpsClient,
+        max_rounds: usize,
+    ) -> ToolContext {
+        ToolContext {
+            db,
+            table: table.to_string(),
+            provider: provider.to_string(),
+            api_url: api_url.to_string(),
+            api_key: api_key.to_string(),
+            embed_model: embed_model.to_string(),
+            client: client.clone(),
+            max_rounds,
+            query_params: QueryParams::default(),
+        }
+    }
+
+    /// Set the parameters of the vector search of the search_code tool
+    pub fn with_query_params(mut self, query_params: QueryParams) -> Self {
+        self.query_params = query_params;
+        self
+    }
+
+    /// Maximum number of tool call rounds before the model has to answer
+    pub fn get_max_rounds(&self) -> usize {
+        self.max_rounds
+    }
+
+    /// The definitions of the built-in tools sent with the chat request
+    pub fn definitions(&self) -> Vec<Value> {
+        vec![
+            function(
+                SEARCH_CODE,
+                "Search the indexed repository for code chunks similar to the query",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "query": { "type": "string", "description": "What to search for" },
+                        "limit": { "type": "integer", "description": "Number of chunks to return" }
+                    },
+                    "required": ["query"]
+                }),
+            ),
+            function(
+                READ_FILE,
+                "Read a range of chunks of an indexed file, chunks are numbered from 0",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "The file path from list_files" },
+                        "start_chunk": { "type": "integer", "description": "The first chunk to read" },
+                        "end_chunk": { "type": "integer", "description": "The last chunk to read" }
+                    },
+                    "required": ["path"]
+                }),
+            ),
+            function(
+                LIST_FILES,
+                "List the files in the indexed repository",
+                json!({ "type": "object", "properties": {} }),
+            ),
+        ]
+    }
+
+    /// Run the tool call, errors are returned to the model as the tool result
+    /// so it can correct the call
+    /// # Arguments
+    /// * `call` - The tool call requested by the model
+    /// # Returns
+    /// * `String` - The tool result
+    pub async fn execute(&self, call: &ToolCall) -> String {
+        info!("Calling tool {}", call.get_name());
+        let result = match call.get_arguments() {
+            Ok(arguments) => self.run(call.get_name(), &arguments).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(output) => {
+                debug!("Tool {} returned {} chars", call.get_name(), output.len());
+                output
+            }
+            Err(e) => format!("Error: {:#}", e),
+        }
+    }
+
+    async fn run(&self, name: &str, arguments: &Value) -> Result<String> {
+        match name {
+            SEARCH_CODE => {
+                let query = string_argument(arguments, "query")?;
+                let limit = arguments
+                    .get("limit")
+                    .and_then(|l| l.as_u64())
+                    .map_or(DEFAULT_SEARCH_LIMIT, |l| l as usize);
+                self.search_code(query, limit).await
+            }
+            READ_FILE => {
+                let path = string_argument(arguments, "path")?;
+                let start = int_argument(arguments, "start_chunk").unwrap_or(0);
+                let end = int_argument(arguments, "end_chunk");
+                self.read_file(path, start, end).await
+            }
+            LIST_FILES => {
+                let files =
+                    query::list_files(&self.db, &self.table, &self.query_params.snapshot).await?;
+                Ok(files.join("\n"))
+            }
+            _ => Err(anyhow!("Unknown tool: {}", name)),
+        }
+    }
+
+    async fn search_code(&self, search: &str, limit: usize) -> Result<String> {
+        let mut db = self.db.clone();
+        let records = query::run_query(
+            &mut db,
+            &self.provider,
+            &self.api_url,
+            &self.api_key,
+            &self.embed_model,
+            &vec![search.to_string()],
+            &self.table,
+            &self.client,
+            false,
+            false,
+            &self.query_params,
+        )
+        .await?;
+
+        let results: Vec<String> = records.iter().take(limit).map(format_record).collect();
+        if results.is_empty() {
+            return Ok("No results".to_string());
+        }
+        Ok(results.join("\n\n"))
+    }
+
+    async fn read_file(&self, path: &str, start: i32, end: Option<i32>) -> Result<String> {
+        let records = query::query_file_chunks(
+            &self.db,
+            &self.table,
+            path,
+            start,
+            end,
+            &self.query_params.snapshot,
+        )
+        .await?;
+        if records.is_empty() {
+            return Err(anyhow!(
+                "No chunks found for {}, use list_files to get the file paths",
+                path
+            ));
+        }
+        Ok(records
+            .iter()
+            .map(format_record)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+}
+
+fn function(name: &str, description: &str, parameters: Value) -> Value {
+    json!({
+        "type": "function",
+        "function": {
+            "name": name,
+            "description": description,
+            "parameters": parameters
+        }
+    })
+}
+
+fn string_argument<'a>(arguments: &'a Value, name: &str) -> Result<&'a str> {
+    arguments
+        .get(name)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("Missing string argument: {}", name))
+}
+
+fn int_argument(arguments: &Value, name: &str) -> Option<i32> {
+    arguments
+        .get(name)
+        .and_then(|v| v.as_i64())
+        .map(|v| v as i32)
+}
+
+fn format_record(record: &QueryRecord) -> String {
+    let mut header = format!("File: {}", record.path());
+    if let Some(chunk) = record.chunk_number {
+        header.push_str(&format!(" chunk: {}", chunk));
+    }
+    if let Some(score) = record.score() {
+        header.push_str(&format!(" score: {:.3}", score));
+    }
+    format!("{}\n{}", header, record.content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lancevectordb::test_tables::{create_file_table, temp_db};
+    use hyper_rustls::HttpsConnectorBuilder;
+    use hyper_util::client::legacy::Client as LegacyClient;
+    use hyper_util::rt::TokioExecutor;
+    use rustls::{ClientConfig, RootCertStore};
+
+    /// A client without roots, the tools under test never reach an HTTPS server
+    fn test_client() -> HttpsClient {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let config = ClientConfig::builder()
+            .with_root_certificates(RootCertStore::empty())
+            .with_no_client_auth();
+        let https = HttpsConnectorBuilder::new()
+            .with_tls_config(config)
+            .https_or_http()
+            .enable_http1()
+            .build();
+        LegacyClient::builder(TokioExecutor::new()).build(https)
+    }
+
+    /// Tools over a table of two files, the embedding API is a closed local port
+    async fn test_tools(name: &str) -> Result<ToolContext> {
+        let db = temp_db(name).await?;
+        create_file_table(
+            &db,
+            name,
+            &[("src/main.rs", 1..11), ("src/chat/mod.rs", 1..6)],
+        )
+        .await?;
+        Ok(ToolContext::new(
+            db,
+            name,
+            "ollama",
+            "http://127.0.0.1:9",
+            "",
+            "nomic-embed-text",
+            &test_client(),
+            3,
+        ))
+    }
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        serde_json::from_value(json!({
+            "function": { "name": name, "arguments": arguments }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn list_files_lists_the_paths_of_the_table() -> Result<()> {
+        let tools = test_tools("tools-list").await?;
+        let output = tools.execute(&call(LIST_FILES, json!({}))).await;
+        assert_eq!(output, "src/chat/mod.rs\nsrc/main.rs");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_file_reads_the_chunks_of_the_path() -> Result<()> {
+        let tools = test_tools("tools-read").await?;
+        let arguments = json!({ "path": "src/main.rs", "start_chunk": 1, "end_chunk": 1 });
+        let output = tools.execute(&call(READ_FILE, arguments)).await;
+        assert!(
+            output.starts_with("File: src/main.rs chunk: 1\n"),
+            "{}",
+            output
+        );
+        assert!(output.contains("src/main.rs line 6"), "{}", output);
+        assert!(!output.contains("src/main.rs line 5\n"), "{}", output);
+
+        let arguments = json!({ "path": "src/missing.rs" });
+        let output = tools.execute(&call(READ_FILE, arguments)).await;
+        assert!(output.starts_with("Error: No chunks found for src/missing.rs"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn search_code_errors_are_returned_to_the_model() -> Result<()> {
+        let tools = test_tools("tools-search").await?;
+        let output = tools.execute(&call(SEARCH_CODE, json!({}))).await;
+        assert_eq!(output, "Error: Missing string argument: query");
+
+        // the embedding API is unreachable, the error is the tool result
+        let output = tools
+            .execute(&call(SEARCH_CODE, json!({ "query": "main" })))
+            .await;
+        assert!(output.starts_with("Error: "), "{}", output);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_tools_and_arguments_are_errors() -> Result<()> {
+        let tools = test_tools("tools-unknown").await?;
+        let output = tools.execute(&call("write_file", json!({}))).await;
+        assert_eq!(output, "Error: Unknown tool: write_file");
+
+        let output = tools.execute(&call(READ_FILE, json!("{not json"))).await;
+        assert!(output.starts_with("Error: Failed to parse tool arguments"));
+        Ok(())
+    }
+
+    #[test]
+    fn records_are_shown_with_their_path() {
+        let record = QueryRecord {
+            id: Some("src/chat/mod.rs:1".to_string()),
+            content: "fn main() {}".to_string(),
+            metadata: "mod.rs".to_string(),
+            chunk_number: Some(2),
+            distance: None,
+            similarity: Some(0.5),
+            file_path: Some("src/chat/mod.rs".to_string()),
+            start_line: None,
+            end_line: None,
+            rerank_score: None,
+        };
+        assert_eq!(
+            format_record(&record),
+            "File: src/chat/mod.rs chunk: 2 score: 0.500\nfn main() {}"
+        );
+    }
+}
@@ -53,22 +53,54 @@ impl Language {
             _ => Language::UNKNOWN,
         }
     }
+
+    /// Name of the language as used to tag fenced code blocks
+    fn name(&self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Python => "python",
+            Language::Cpp => "cpp",
+            Language::Java => "java",
+            Language::JavaScript => "javascript",
+            Language::TypeScript => "typescript",
+            Language::Tsx => "tsx",
+            Language::C | Language::Header => "c",
+            Language::Go => "go",
+            Language::Scala => "scala",
+            Language::Text => "text",
+            Language::SPARKLOG => "log",
+            Language::UNKNOWN => "",
+        }
+    }
+}
+
+/// Get the language name of a file from its extension, empty if the language is unknown
+pub fn get_language_name(file_name: &str) -> &'static str {
+    let (language, _) = is_supported_file(Path::new(file_name));
+    language.name()
 }
 
 pub struct FileChunk {
     content: Vec<String>,
     file_path: PathBuf,
     chunk_number: i32,
+    start_line: Option<i32>,
 }
 
 /// A struct that represents a codebase.
 impl FileChunk {
-    fn new(content: String, file_path: PathBuf, chunk_number: i32) -> Self {
+    fn new(
+        content: String,
+        file_path: PathBuf,
+        chunk_number: i32,
+        start_line: Option<i32>,
+    ) -> Self {
         let content_lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
         Self {
             content: content_lines,
             file_path,
             chunk_number,
+            start_line,
         }
     }
 
@@ -84,6 +116,11 @@ impl FileChunk {
         self.chunk_number
     }
 
+    /// Line number of the first line of the chunk in the file, starting at 1
+    pub fn get_start_line(&self) -> Option<i32> {
+        self.start_line
+    }
+
     pub fn print_file_chunk(&self) {
         println!(
             "File: {}, Chunk {}: {}",
@@ -124,6 +161,87 @@ pub fn chunk_embed_request(
                 .to_string(),
         ),
         chunk_number: Some(chunk.chunk_number),
+        file_path: Some(chunk.file_path.display().to_string()),
+        start_line: chunk.start_line,
+        language: Some(get_language_name(&chunk.get_file_name()))
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string()),
+        symbol_kinds: detect_symbol_kinds(&chunk.content)
+            .into_iter()
+            .map(|k| k.map(|k| k.to_string()))
+            .collect(),
+    }
+}
+
+/// Words before the declaration keyword that are skipped like visibility and modifiers
+const DECLARATION_MODIFIERS: [&str; 16] = [
+    "pub",
+    "async",
+    "unsafe",
+    "extern",
+    "export",
+    "default",
+    "public",
+    "private",
+    "protected",
+    "static",
+    "final",
+    "abstract",
+    "sealed",
+    "case",
+    "override",
+    "implicit",
+];
+
+/// Characters of code on a declaration line, a line with more words and none of them is prose
+const CODE_PUNCTUATION: [char; 9] = ['(', ')', '{', '}', ':', ';', '=', '<', '['];
+
+/// Kind of the declaration every line of the chunk belongs to like function, struct or class
+/// A line belongs to the last declaration at or before it in the chunk,
+/// the lines before the first declaration of the chunk have no kind
+fn detect_symbol_kinds(lines: &[String]) -> Vec<Option<&'static str>> {
+    let mut kind = None;
+    lines
+        .iter()
+        .map(|line| {
+            if let Some(declaration) = declaration_line_kind(line) {
+                kind = Some(declaration);
+            }
+            kind
+        })
+        .collect()
+}
+
+/// Kind of the declaration on the line, the declaration keywords of the supported languages
+/// are matched after the modifiers and must be followed by a name.
+/// Prose like "type the name of the file" is not a declaration,
+/// a declaration is the keyword and its name or a line with code punctuation.
+fn declaration_line_kind(line: &str) -> Option<&'static str> {
+    let mut words = line
+        .split_whitespace()
+        .skip_while(|word| DECLARATION_MODIFIERS.contains(word) || word.starts_with("pub("));
+    let kind = words.next().and_then(declaration_kind)?;
+    let name = words.next()?;
+    if !name.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '(') {
+        return None;
+    }
+    let short = words.next().is_none();
+    (short || line.contains(CODE_PUNCTUATION)).then_some(kind)
+}
+
+fn declaration_kind(keyword: &str) -> Option<&'static str> {
+    match keyword {
+        "fn" | "def" | "func" | "function" => Some("function"),
+        "struct" => Some("struct"),
+        "enum" => Some("enum"),
+        "trait" => Some("trait"),
+        "impl" => Some("impl"),
+        "mod" | "module" | "namespace" => Some("module"),
+        "class" => Some("class"),
+        "interface" => Some("interface"),
+        "object" => Some("object"),
+        "type" => Some("type"),
+        _ => None,
     }
 }
 
@@ -228,13 +346,14 @@ async fn split_file_into_chunks(
         // user tree_sitter_markdown
         let splitter = text_splitter::TextSplitter::new(chunk_config);
         let chunks = splitter
-            .chunks(&content)
+            .chunk_indices(&content)
             .enumerate()
-            .map(|(i, chunk)| {
+            .map(|(i, (offset, chunk))| {
                 Ok(FileChunk::new(
                     chunk.to_string(),
                     file_path.clone(),
                     i as i32,
+                    Some(line_number_at(&content, offset)),
                 ))
             })
             .collect::<Result<Vec<FileChunk>, CodeSplitterError>>()?;
@@ -249,15 +368,16 @@ async fn split_file_into_chunks(
         )
         .context("Failed to create code splitter")?;
 
-        let code_chunks = splitter.chunks(&content);
+        let code_chunks = splitter.chunk_indices(&content);
 
         let chunks: Vec<FileChunk> = code_chunks
             .enumerate()
-            .map(|(i, chunk)| {
+            .map(|(i, (offset, chunk))| {
                 Ok(FileChunk::new(
                     chunk.to_string(),
                     file_path.clone(),
                     i as i32,
+                    Some(line_number_at(&content, offset)),
                 ))
             })
             .collect::<Result<Vec<FileChunk>, CodeSplitterError>>()?;
@@ -272,6 +392,11 @@ async fn split_file_into_chunks(
     Ok(vec![])
 }
 
+/// Line number of the byte offset in the content, starting at 1
+fn line_number_at(content: &str, offset: usize) -> i32 {
+    content[..offset].matches('\n').count() as i32 + 1
+}
+
 /// Checks if the given file is a supported code file based on its extension.
 ///
 /// # Arguments
@@ -335,10 +460,12 @@ async fn process_spark_log_file(
         .chunks(&error_lines)
         .enumerate()
         .map(|(i, chunk)| {
+            // the log is filtered so the lines do not match the file
             Ok(FileChunk::new(
                 chunk.to_string(),
                 file_path.clone(),
                 i as i32,
+                None,
             ))
         })
         .collect::<Result<Vec<FileChunk>, CodeSplitterError>>()?;
@@ -372,3 +499,74 @@ fn capture_context_lines(content: &str, num_lines: usize) -> String {
 
     result.join("\n")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn every_row_has_the_kind_of_its_declaration() {
+        let chunk = lines(
+            "use std::io;\n\
+             /// The entry point\n\
+             pub async fn main() {\n\
+             \x20   let config = Config::default();\n\
+             }\n\
+             pub(crate) struct Config {\n\
+             \x20   name: String,\n\
+             }",
+        );
+        assert_eq!(
+            detect_symbol_kinds(&chunk),
+            vec![
+                None,
+                None,
+                Some("function"),
+                Some("function"),
+                Some("function"),
+                Some("struct"),
+                Some("struct"),
+                Some("struct"),
+            ]
+        );
+    }
+
+    #[test]
+    fn declarations_of_other_languages_are_detected() {
+        assert_eq!(declaration_line_kind("def load(path):"), Some("function"));
+        assert_eq!(declaration_line_kind("class Loader(Base):"), Some("class"));
+        assert_eq!(
+            declaration_line_kind("func (l *Loader) Load() error {"),
+            Some("function")
+        );
+        assert_eq!(declaration_line_kind("type Loader struct {"), Some("type"));
+        assert_eq!(
+            declaration_line_kind("export default function load() {"),
+            Some("function")
+        );
+        assert_eq!(declaration_line_kind("module Loader"), Some("module"));
+        assert_eq!(
+            declaration_line_kind("case class Config(name: String)"),
+            Some("class")
+        );
+    }
+
+    #[test]
+    fn prose_is_not_a_declaration() {
+        assert_eq!(declaration_line_kind("type the name of the file"), None);
+        assert_eq!(
+            declaration_line_kind("function calls are slow in this loop"),
+            None
+        );
+        assert_eq!(declaration_line_kind("object."), None);
+        assert_eq!(declaration_line_kind("module 2 covers the loader"), None);
+        assert_eq!(declaration_line_kind("type: string"), None);
+
+        let chunk = lines("Loading\ntype the name of the file\nthen press enter");
+        assert_eq!(detect_symbol_kinds(&chunk), vec![None, None, None]);
+    }
+}
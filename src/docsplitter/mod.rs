@@ -1,2 +1,2 @@
 #![allow(dead_code)]
-pub mod code_loader;
\ No newline at end of file
+pub mod code_loader;
@@ -13,6 +13,18 @@ pub struct EmbedRequest {
     pub input: Vec<String>,
     pub metadata: Option<String>, // TODO - add metadata hashmap column JSON
     pub chunk_number: Option<i32>,
+    /// Path of the source file, stored with the chunk for citations
+    #[serde(skip)]
+    pub file_path: Option<String>,
+    /// Line number of the first input in the source file
+    #[serde(skip)]
+    pub start_line: Option<i32>,
+    /// Language of the source file like rust or python
+    #[serde(skip)]
+    pub language: Option<String>,
+    /// Kind of the declaration every input line belongs to like function or struct
+    #[serde(skip)]
+    pub symbol_kinds: Vec<Option<String>>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug)]
@@ -63,6 +75,10 @@ impl EmbedRequest {
             input,
             metadata: Some(metadata.to_string()),
             chunk_number,
+            file_path: None,
+            start_line: None,
+            language: None,
+            symbol_kinds: Vec::new(),
         };
 
         std::sync::Arc::new(RwLock::new(data))
@@ -88,6 +104,10 @@ impl EmbedRequest {
             input,
             metadata: None,
             chunk_number,
+            file_path: None,
+            start_line: None,
+            language: None,
+            symbol_kinds: Vec::new(),
         }
     }
 
@@ -101,6 +121,10 @@ impl EmbedRequest {
             input: vec![],
             metadata: None,
             chunk_number: None,
+            file_path: None,
+            start_line: None,
+            language: None,
+            symbol_kinds: Vec::new(),
         }
     }
 
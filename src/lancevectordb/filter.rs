@@ -0,0 +1,150 @@
+use crate::lancevectordb::predicate::Predicate;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// SearchFilter restricts the searched rows by file, language, model, load time and symbol kind
+/// Filters are given as `key=value` like `path=src/**/*.rs` or `created_after=2024-06-01`
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    /// Glob matched against the file path, `*` and `**` match any characters and `?` one
+    pub path: Option<String>,
+    pub language: Option<String>,
+    pub model: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub symbol_kind: Option<String>,
+}
+
+impl SearchFilter {
+    /// Parse the `key=value` filters, a key given twice keeps the last value
+    /// # Arguments
+    /// * `filters` - The filters from the command line
+    /// # Returns
+    /// * `Result<SearchFilter>` - The filter, an unknown key or invalid date is an error
+    pub fn parse(filters: &[String]) -> Result<SearchFilter> {
+        let mut filter = SearchFilter::default();
+        for item in filters {
+            let (key, value) = item
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Filter must be key=value: {}", item))?;
+            let value = value.trim().to_string();
+            match key.trim() {
+                "path" => filter.path = Some(value),
+                "language" => filter.language = Some(value.to_lowercase()),
+                "model" => filter.model = Some(value),
+                "created_after" => filter.created_after = Some(parse_time(&value)?),
+                "created_before" => filter.created_before = Some(parse_time(&value)?),
+                "symbol_kind" => filter.symbol_kind = Some(value.to_lowercase()),
+                other => {
+                    return Err(anyhow!(
+                    "Unknown filter {}, use path, language, model, created_after, created_before or symbol_kind",
+                    other
+                ))
+                }
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Compile the filter into a Lance predicate, None if nothing is filtered
+    pub fn to_predicate(&self) -> Option<Predicate> {
+        let mut predicates = Vec::new();
+        if let Some(path) = &self.path {
+            predicates.push(Predicate::like("file_path", &glob_to_like(path)));
+        }
+        if let Some(language) = &self.language {
+            predicates.push(Predicate::eq("language", language));
+        }
+        if let Some(model) = &self.model {
+            predicates.push(Predicate::eq("model", model));
+        }
+        if self.created_after.is_some() || self.created_before.is_some() {
+            predicates.push(Predicate::range(
+                "created_at",
+                self.created_after,
+                self.created_before,
+            ));
+        }
+        if let Some(symbol_kind) = &self.symbol_kind {
+            predicates.push(Predicate::eq("symbol_kind", symbol_kind));
+        }
+
+        Predicate::all(predicates)
+    }
+}
+
+/// Convert a glob to a LIKE pattern, the LIKE wildcards in the glob are escaped
+fn glob_to_like(glob: &str) -> String {
+    let mut pattern = String::with_capacity(glob.len());
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => {
+                // ** and * both match any characters, LIKE has no path separator
+                while chars.peek() == Some(&'*') {
+                    chars.next();
+                }
+                pattern.push('%');
+            }
+            '?' => pattern.push('_'),
+            '%' | '_' | '\\' => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            _ => pattern.push(c),
+        }
+    }
+    pattern
+}
+
+/// Parse a date like `2024-06-01` or a RFC 3339 time like `2024-06-01T12:00:00Z`
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date {}, use YYYY-MM-DD or RFC 3339", value))?;
+    Ok(date.and_time(chrono::NaiveTime::MIN).and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn glob_wildcards_become_like_wildcards() {
+        assert_eq!(glob_to_like("src/**/*.rs"), "src/%/%.rs");
+        assert_eq!(glob_to_like("src/?.rs"), "src/_.rs");
+        assert_eq!(glob_to_like("src/main.rs"), "src/main.rs");
+    }
+
+    #[test]
+    fn like_wildcards_in_the_glob_are_escaped() {
+        assert_eq!(glob_to_like("100%_done.rs"), "100\\%\\_done.rs");
+        assert_eq!(glob_to_like("a\\b"), "a\\\\b");
+        assert_eq!(glob_to_like("src/*_test.rs"), "src/%\\_test.rs");
+    }
+
+    #[test]
+    fn dates_start_at_midnight_utc() {
+        assert_eq!(
+            parse_time("2024-06-01").unwrap(),
+            Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn rfc3339_times_are_converted_to_utc() {
+        assert_eq!(
+            parse_time("2024-06-01T12:00:00+02:00").unwrap(),
+            Utc.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_times_are_errors() {
+        assert!(parse_time("2024-13-01").is_err());
+        assert!(parse_time("yesterday").is_err());
+    }
+}
@@ -1,7 +1,12 @@
-use crate::app::constants::VECTOR_DB_DIM_SIZE;
+use crate::app::constants::{
+    DISTANCE_TYPE_KEY, INDEX_HNSW_PARTITION_ROWS, INDEX_MIN_ROWS, LANCEDB_DISTANCE_FN,
+    VECTOR_DB_DIM_SIZE, VECTOR_INDEX_KEY,
+};
 use crate::embedder::config::{EmbedRequest, EmbedResponse};
+use crate::lancevectordb::predicate::Predicate;
+use crate::lancevectordb::query::{table_distance_type, table_embed_model};
 use anyhow::Result;
-use anyhow::{Context, Ok};
+use anyhow::{anyhow, Context, Ok};
 use arrow::array::{FixedSizeListArray, StringArray, TimestampSecondArray};
 use arrow_array::types::Float32Type;
 use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
@@ -10,7 +15,10 @@ use arrow_schema::TimeUnit;
 use arrow_schema::{DataType, Field};
 use lancedb::index::scalar::FtsIndexBuilder;
 use lancedb::index::Index;
-use lancedb::{Connection, Table};
+use lancedb::table::AddDataMode;
+use lancedb::{Connection, DistanceType, Table};
+use sha2::{Digest, Sha256};
+use std::path::Path;
 use std::sync::Arc;
 use std::vec;
 use tokio::sync::RwLock;
@@ -25,13 +33,19 @@ pub struct TableSchema {
     pub vector: Arc<Field>,
     pub created_at: Arc<Field>,
     pub chunk_number: Arc<Field>,
+    pub file_path: Arc<Field>,
+    pub start_line: Arc<Field>,
+    pub end_line: Arc<Field>,
+    pub language: Arc<Field>,
+    pub symbol_kind: Arc<Field>,
+    pub source: Arc<Field>,
 }
 
 impl TableSchema {
     pub fn new(table_name: &String) -> Self {
         TableSchema {
             name: table_name.to_string(),
-            id: Arc::new(Field::new("id", DataType::Int32, false)),
+            id: Arc::new(Field::new("id", DataType::Utf8, false)),
             content: Arc::new(Field::new("content", DataType::Utf8, false)),
             metadata: Arc::new(Field::new("metadata", DataType::Utf8, false)),
             vector: Arc::new(Field::new(
@@ -49,6 +63,12 @@ impl TableSchema {
                 false,
             )),
             chunk_number: Arc::new(Field::new("chunk_number", DataType::Int32, true)),
+            file_path: Arc::new(Field::new("file_path", DataType::Utf8, true)),
+            start_line: Arc::new(Field::new("start_line", DataType::Int32, true)),
+            end_line: Arc::new(Field::new("end_line", DataType::Int32, true)),
+            language: Arc::new(Field::new("language", DataType::Utf8, true)),
+            symbol_kind: Arc::new(Field::new("symbol_kind", DataType::Utf8, true)),
+            source: Arc::new(Field::new("source", DataType::Utf8, true)),
         }
     }
 
@@ -61,38 +81,23 @@ impl TableSchema {
             Arc::clone(&self.model),
             Arc::clone(&self.created_at),
             Arc::clone(&self.chunk_number),
+            Arc::clone(&self.file_path),
+            Arc::clone(&self.start_line),
+            Arc::clone(&self.end_line),
+            Arc::clone(&self.language),
+            Arc::clone(&self.symbol_kind),
+            Arc::clone(&self.source),
         ])
     }
 
     fn get_table_name(&self) -> &str {
         self.name.as_str()
     }
-
-    fn empty_batch(&self) -> Result<RecordBatch> {
-        RecordBatch::try_new(
-            Arc::new(self.create_schema()),
-            vec![
-                Arc::new(Int32Array::from_iter_values(0..256)),
-                Arc::new(StringArray::from_iter_values((0..256).map(|_| ""))),
-                Arc::new(StringArray::from_iter_values((0..256).map(|_| ""))),
-                Arc::new(
-                    FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
-                        (0..256).map(|_| Some(vec![Some(1.0); VECTOR_DB_DIM_SIZE as usize])),
-                        VECTOR_DB_DIM_SIZE,
-                    ),
-                ),
-                Arc::new(StringArray::from_iter_values((0..256).map(|_| ""))),
-                Arc::new(TimestampSecondArray::from_iter_values(
-                    (0..256).map(|_| chrono::Utc::now().timestamp()),
-                )),
-                Arc::new(Int32Array::from_iter_values((0..256).map(|_| 0))),
-            ],
-        )
-        .context("Failed to create a RecordBatch")
-    }
 }
 
 /// Create a table in the database with the given schema
+/// The table starts empty, the vector index is built once the rows are loaded
+/// An existing table is overwritten with an empty version so its earlier versions stay readable
 /// Arguments:
 /// - db: &mut Connection
 /// - table_schema: &TableSchema
@@ -100,40 +105,97 @@ impl TableSchema {
 /// - Result<(), Box<dyn Error>>
 pub async fn create_lance_table(db: &mut Connection, table_schema: &TableSchema) -> Result<()> {
     let table_name = table_schema.get_table_name();
+    let arrow_schema = Arc::new(table_schema.create_schema());
     let all_tables = db.table_names().execute().await?;
     if all_tables.contains(&table_name.to_string()) {
-        db.drop_table(table_name)
+        let table = db
+            .open_table(table_name)
+            .execute()
             .await
-            .context("Failed to drop a table")?;
+            .context("Failed to open a table")?;
+        let empty = RecordBatchIterator::new(vec![], arrow_schema);
+        table
+            .add(Box::new(empty))
+            .mode(AddDataMode::Overwrite)
+            .execute()
+            .await
+            .context("Failed to overwrite the table")?;
+        log::info!("Table overwritten, the earlier versions are kept");
+        return Ok(());
     }
 
-    let arrow_schema = Arc::new(table_schema.create_schema());
-    db.create_empty_table(table_name, arrow_schema.clone())
+    db.create_empty_table(table_name, arrow_schema)
         .execute()
         .await
         .context("Failed to create a table")?;
 
-    // insert into table
-    let table = db.open_table(table_name).execute().await?;
-    let mut writer = table.merge_insert(&["id", "content"]);
-    writer.when_not_matched_insert_all();
-    writer.when_matched_update_all(None);
+    log::info!("Table created successfully");
 
-    // add rows to the writer
-    let batch = table_schema.empty_batch()?;
-    let record_batch = RecordBatchIterator::new(
-        vec![batch].into_iter().map(std::result::Result::Ok),
-        arrow_schema.clone(),
-    );
+    Ok(())
+}
 
-    // Pass the record batch to the writer.
-    writer
-        .execute(Box::new(record_batch))
+/// Open the table to append a source to, the table is created if it does not exist
+/// The rows of an earlier load of the same source are deleted so the source is replaced
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_schema` - The schema of the table
+/// * `source` - The name of the appended source
+/// * `distance_type` - The distance type of the vector index, it has to match the existing index
+/// * `embed_model` - The embedding model of the source, it has to match the model of the table
+/// # Returns
+/// * `Result<()>` - The result of the operation
+pub async fn prepare_append(
+    db: &mut Connection,
+    table_schema: &TableSchema,
+    source: &str,
+    distance_type: DistanceType,
+    embed_model: &str,
+) -> Result<()> {
+    let table_name = table_schema.get_table_name();
+    let all_tables = db.table_names().execute().await?;
+    if !all_tables.contains(&table_name.to_string()) {
+        return create_lance_table(db, table_schema).await;
+    }
+
+    let table = db
+        .open_table(table_name)
+        .execute()
         .await
-        .context("Failed to insert records")?;
+        .with_context(|| format!("Failed to open table: {:?}", table_name))?;
+    let schema = table.schema().await.context("Failed to get table schema")?;
+    if schema.field_with_name(table_schema.source.name()).is_err() {
+        return Err(anyhow!(
+            "Table {} has no source column, load it again without append",
+            table_name
+        ));
+    }
+    let id_type = schema.field_with_name(table_schema.id.name())?.data_type();
+    if id_type != table_schema.id.data_type() {
+        return Err(anyhow!(
+            "Table {} has {} ids instead of stable ids, load it again without append",
+            table_name,
+            id_type
+        ));
+    }
+    if let Some(indexed) = table_distance_type(&table).await? {
+        if indexed != distance_type {
+            return Err(anyhow!(
+                "Table {} is indexed with the {} distance, it cannot be appended with {}",
+                table_name,
+                indexed,
+                distance_type
+            ));
+        }
+    }
 
-    log::info!("Table created successfully");
+    check_embed_model(&table, table_schema, embed_model).await?;
+
+    table
+        .delete(&Predicate::eq(table_schema.source.name(), source).to_string())
+        .await
+        .with_context(|| format!("Failed to delete the earlier rows of {}", source))?;
 
+    log::info!("Appending {} to table {}", source, table_name);
     Ok(())
 }
 
@@ -153,36 +215,80 @@ pub async fn insert_embeddings(
     let record_iter = vec![records].into_iter().map(std::result::Result::Ok);
     let record_batch = RecordBatchIterator::new(record_iter, arrow_schema);
 
-    let mut writer =
-        table.merge_insert(&["content", "metadata", "vector", "model", "chunk_number"]);
+    // the stable id is the merge key so a row loaded again replaces its earlier copy
+    let mut writer = table.merge_insert(&[table_schema.id.name().as_str()]);
     // add merge options to writer
+    writer.when_matched_update_all(None);
     writer.when_not_matched_insert_all();
 
-    let write_result = writer.execute(Box::new(record_batch)).await;
-
-    if let Err(e) = write_result {
-        log::error!("Failed to insert records: {:?}", e);
-        return Ok(());
-    }
+    // a failed merge fails the load so the chunk count is not recorded for rows that were not written
+    writer
+        .execute(Box::new(record_batch))
+        .await
+        .context("Failed to insert records")?;
 
     log::info!("Records inserted successfully");
 
     Ok(())
 }
 
+/// The stable id of a row, the sha256 of the source, the file path, the chunk, the line range and the sha256 of the content
+/// A row keeps its id across loads as long as its source, file, chunk, lines and content are unchanged
+/// The chunks overlap, so a line in two chunks is two rows with their own ids
+/// # Arguments
+/// * `source` - The name of the loaded source, sources appended to one table can have the same paths
+/// * `path` - The file path relative to the loaded directory, the file name for files loaded without a path
+/// * `chunk_number` - The chunk of the row
+/// * `range` - The lines of the row like `12-12`
+/// * `content` - The content of the row
+/// # Returns
+/// * `String` - The hex encoded id
+pub fn stable_row_id(
+    source: &str,
+    path: &str,
+    chunk_number: i32,
+    range: &str,
+    content: &str,
+) -> String {
+    let content_hash = Sha256::digest(content.as_bytes());
+    let mut hasher = Sha256::new();
+    for part in [source, path, &chunk_number.to_string(), range] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(content_hash);
+    format!("{:x}", hasher.finalize())
+}
+
+/// The path of the file relative to the loaded directory, so loading ./src or /abs/src gives the same ids
+/// A loaded file is relative to itself, its file name is used
+fn relative_path(path: &str, root: &Path) -> String {
+    let path = Path::new(path);
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    if relative.as_os_str().is_empty() {
+        return path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().to_string(),
+        );
+    }
+    relative.display().to_string()
+}
+
 /// Create a RecordBatch from the EmbedRequest and EmbedResponse
 /// Arguments:
-/// - id: i32
 /// - request: Arc<RwLock<EmbedRequest>>
 /// - response: EmbedResponse
 /// - table_schema: &TableSchema
+/// - source: &str
+/// - root: &Path - the loaded directory the ids are built relative to
 /// Returns:
 /// - Result<RecordBatch, Box<dyn Error>> - The RecordBatch (Arrow)
 pub async fn create_record_batch(
-    id: i32,
     request: Arc<RwLock<EmbedRequest>>,
     response: EmbedResponse,
     table_schema: &TableSchema,
+    source: &str,
+    root: &Path,
 ) -> Result<RecordBatch> {
     if response.embeddings.is_empty() {
         return Err(anyhow::Error::msg("No embeddings found in the response"));
@@ -192,7 +298,6 @@ pub async fn create_record_batch(
     // let num_embeddings = response.embeddings.len();
     let len = response.embeddings.len();
 
-    let id_array = Arc::new(Int32Array::from_iter_values((0..len).map(|_| id)));
     let content_array = Arc::new(StringArray::from_iter_values(
         request.input.iter().take(len).map(|s| s.to_string()),
     ));
@@ -202,6 +307,29 @@ pub async fn create_record_batch(
         None => String::from("Empty"),
     };
 
+    // the path in the loaded directory is stored so path filters do not depend on where load ran from
+    let file_path = request
+        .file_path
+        .as_deref()
+        .map(|file_path| relative_path(file_path, root));
+    // every input is one line of the chunk, inputs without lines are told apart by their position
+    let path = file_path.clone().unwrap_or_else(|| dir_name.clone());
+    let chunk_number = request.chunk_number.unwrap_or(0);
+    let id_array = Arc::new(StringArray::from_iter_values(
+        request
+            .input
+            .iter()
+            .take(len)
+            .enumerate()
+            .map(|(i, content)| {
+                let range = match request.start_line {
+                    Some(line) => format!("{}-{}", line + i as i32, line + i as i32),
+                    None => format!("input {}", i),
+                };
+                stable_row_id(source, &path, chunk_number, &range, content)
+            }),
+    ));
+
     let metadata_array = Arc::new(StringArray::from_iter_values(
         std::iter::repeat(dir_name).take(len).map(|s| s.to_string()),
     ));
@@ -242,6 +370,25 @@ pub async fn create_record_batch(
         (0..len).map(|_| request.chunk_number.unwrap_or(0)),
     ));
 
+    let file_path_array = Arc::new(StringArray::from_iter((0..len).map(|_| file_path.clone())));
+
+    let language_array = Arc::new(StringArray::from_iter(
+        (0..len).map(|_| request.language.clone()),
+    ));
+
+    let symbol_kind_array = Arc::new(StringArray::from_iter(
+        (0..len).map(|i| request.symbol_kinds.get(i).cloned().flatten()),
+    ));
+
+    let source_array = Arc::new(StringArray::from_iter_values(
+        (0..len).map(|_| source.to_string()),
+    ));
+
+    // every input is one line of the chunk
+    let line_array = Arc::new(Int32Array::from_iter(
+        (0..len).map(|i| request.start_line.map(|line| line + i as i32)),
+    ));
+
     let record_batch = RecordBatch::try_new(
         Arc::new(table_schema.create_schema()),
         vec![
@@ -252,6 +399,12 @@ pub async fn create_record_batch(
             model_array,
             created_at_array,
             chunk_number_array,
+            file_path_array,
+            line_array.clone(),
+            line_array,
+            language_array,
+            symbol_kind_array,
+            source_array,
         ],
     )
     .context("Failed to create a Embedding Records")?;
@@ -259,6 +412,78 @@ pub async fn create_record_batch(
     Ok(record_batch)
 }
 
+/// IndexKind is the type of the vector index built on the embedding column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum IndexKind {
+    /// IVF partitions with product quantization, the smallest index
+    IvfPq,
+    /// HNSW graphs in IVF partitions with product quantization
+    IvfHnswPq,
+    /// HNSW graphs in IVF partitions with scalar quantization, the best recall
+    IvfHnswSq,
+    /// No index, queries scan all the vectors
+    Flat,
+}
+
+impl std::fmt::Display for IndexKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IndexKind::IvfPq => "ivf_pq",
+            IndexKind::IvfHnswPq => "ivf_hnsw_pq",
+            IndexKind::IvfHnswSq => "ivf_hnsw_sq",
+            IndexKind::Flat => "flat",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// IndexParams tunes the vector index, partitions and sub-vectors are derived from the table if not set
+#[derive(Debug, Clone)]
+pub struct IndexParams {
+    pub kind: IndexKind,
+    pub num_partitions: Option<u32>,
+    pub num_sub_vectors: Option<u32>,
+    /// Tables with fewer rows are not indexed since a scan is fast and exact
+    pub min_rows: usize,
+}
+
+impl Default for IndexParams {
+    fn default() -> Self {
+        IndexParams {
+            kind: IndexKind::IvfHnswSq,
+            num_partitions: None,
+            num_sub_vectors: None,
+            min_rows: INDEX_MIN_ROWS,
+        }
+    }
+}
+
+impl IndexParams {
+    /// The IVF partitions, the square root of the rows for IVF_PQ and
+    /// one partition per INDEX_HNSW_PARTITION_ROWS rows for the HNSW indices
+    fn partitions(&self, rows: usize) -> u32 {
+        self.num_partitions.unwrap_or_else(|| {
+            let partitions = match self.kind {
+                IndexKind::IvfHnswPq | IndexKind::IvfHnswSq => {
+                    rows.div_ceil(INDEX_HNSW_PARTITION_ROWS)
+                }
+                _ => (rows as f64).sqrt() as usize,
+            };
+            partitions.max(1) as u32
+        })
+    }
+
+    /// The PQ sub-vectors, 16 or 8 dimensions per sub-vector when the dimension allows it
+    fn sub_vectors(&self, dimension: i32) -> u32 {
+        self.num_sub_vectors.unwrap_or(match dimension {
+            d if d % 16 == 0 => (d / 16) as u32,
+            d if d % 8 == 0 => (d / 8) as u32,
+            _ => 1,
+        })
+    }
+}
+
 /// Create an index on the embedding column
 /// IVF_PQ Index: LanceDB also supports the IVF_PQ (Inverted File with Product Quantization) index,
 /// which divides the dataset into partitions and applies product quantization for efficient vector compression.
@@ -267,46 +492,212 @@ pub async fn create_record_batch(
 /// LanceDB does not automatically create the ANN index.
 /// need to explicitly create the index with the appropriate index type
 /// (e.g., IVF_HNSW_SQ)
-/// Arguments:
-/// - db: &mut Connection
-/// - table_name: &str
-/// - column: Vec<&str>
-/// Returns:
-/// - Result<(), Box<dyn Error>>
+/// Tables below the row threshold are left flat, queries scan them instead
+/// The distance type and index kind are recorded in the schema metadata so queries use the same metric
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table
+/// * `column` - The embedding column
+/// * `distance_type` - The distance type of the index
+/// * `params` - The index kind, partitions, sub-vectors and row threshold
+/// # Returns
+/// * `Result<IndexKind>` - The kind of index built, flat if the table is below the threshold
 pub async fn create_index_on_embedding(
     db: &mut Connection,
     table_name: &str,
     column: Vec<&str>,
-) -> Result<()> {
+    distance_type: DistanceType,
+    params: &IndexParams,
+) -> Result<IndexKind> {
     let table = db.open_table(table_name).execute().await?;
+    let rows = table
+        .count_rows(None)
+        .await
+        .context("Failed to count table rows")?;
+
+    let kind = if params.kind != IndexKind::Flat && rows < params.min_rows {
+        log::info!(
+            "Skipping the {} index of table {:?}, {} rows are below the threshold of {}",
+            params.kind,
+            table_name,
+            rows,
+            params.min_rows
+        );
+        IndexKind::Flat
+    } else {
+        params.kind
+    };
 
-    // Initialize the builder first
-    let hns_index = lancedb::index::vector::IvfHnswSqIndexBuilder::default()
-        .distance_type(crate::app::constants::LANCEDB_DISTANCE_FN) // Set the desired distance type, e.g., L2
-        .num_partitions(100) // Set the number of partitions, e.g., 100
-        .sample_rate(256) // Set the sample rate
-        .max_iterations(50) // Set the max iterations for training
-        .ef_construction(300); // Set the ef_construction value
+    let dimension = table
+        .schema()
+        .await?
+        .field_with_name(column[0])
+        .ok()
+        .and_then(|field| match field.data_type() {
+            DataType::FixedSizeList(_, size) => Some(*size),
+            _ => None,
+        })
+        .unwrap_or(VECTOR_DB_DIM_SIZE);
+    let num_partitions = params.partitions(rows);
+    let num_sub_vectors = params.sub_vectors(dimension);
+
+    let index = match kind {
+        IndexKind::IvfPq => Some(Index::IvfPq(
+            lancedb::index::vector::IvfPqIndexBuilder::default()
+                .distance_type(distance_type)
+                .num_partitions(num_partitions)
+                .num_sub_vectors(num_sub_vectors)
+                .sample_rate(256)
+                .max_iterations(50),
+        )),
+        IndexKind::IvfHnswPq => Some(Index::IvfHnswPq(
+            lancedb::index::vector::IvfHnswPqIndexBuilder::default()
+                .distance_type(distance_type)
+                .num_partitions(num_partitions)
+                .num_sub_vectors(num_sub_vectors)
+                .sample_rate(256)
+                .max_iterations(50)
+                .ef_construction(300),
+        )),
+        IndexKind::IvfHnswSq => Some(Index::IvfHnswSq(
+            lancedb::index::vector::IvfHnswSqIndexBuilder::default()
+                .distance_type(distance_type) // Set the desired distance type, e.g., L2
+                .num_partitions(num_partitions) // Set the number of partitions
+                .sample_rate(256) // Set the sample rate
+                .max_iterations(50) // Set the max iterations for training
+                .ef_construction(300), // Set the ef_construction value
+        )),
+        IndexKind::Flat => None,
+    };
 
-    // Now create the Index using the builder
-    let index = Index::IvfHnswSq(hns_index);
+    if let Some(index) = index {
+        table
+            .create_index(&column, index)
+            .replace(true)
+            .execute()
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to create an index on table: {:?} column: {:?}",
+                    table_name, column
+                )
+            })?;
+        log::info!(
+            "Created {} {} index with {} partitions on table: {:?} column: {:?}",
+            kind,
+            distance_type,
+            num_partitions,
+            table_name,
+            column
+        );
+    }
 
-    table
-        .create_index(&column, index)
+    update_schema_metadata(
+        &table,
+        [
+            (DISTANCE_TYPE_KEY, distance_type.to_string()),
+            (VECTOR_INDEX_KEY, kind.to_string()),
+        ],
+    )
+    .await
+    .context("Failed to record the distance type and index kind")?;
+
+    Ok(kind)
+}
+
+/// Rebuild the vector index of a table
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table
+/// * `distance_type` - The distance type, the recorded distance type of the table if not provided
+/// * `params` - The index kind, partitions, sub-vectors and row threshold
+/// # Returns
+/// * `Result<IndexKind>` - The kind of index built
+pub async fn reindex_table(
+    db: &mut Connection,
+    table_name: &str,
+    distance_type: Option<DistanceType>,
+    params: &IndexParams,
+) -> Result<IndexKind> {
+    let table = db
+        .open_table(table_name)
         .execute()
         .await
-        .with_context(|| {
-            format!(
-                "Failed to create an index on table: {:?} column: {:?}",
-                table_name, column
-            )
-        })?;
+        .with_context(|| format!("Failed to open table: {:?}", table_name))?;
+    let distance_type = match distance_type {
+        Some(distance_type) => distance_type,
+        None => table_distance_type(&table)
+            .await?
+            .unwrap_or(LANCEDB_DISTANCE_FN),
+    };
 
-    log::info!(
-        "Created inverted index on table: {:?} column: {:?}",
-        table_name,
-        column
-    );
+    create_index_on_embedding(db, table_name, vec!["vector"], distance_type, params).await
+}
+
+/// Refuse to append vectors of another model than the vectors of the table
+/// Tables loaded before the model was recorded are checked against the model column
+async fn check_embed_model(
+    table: &Table,
+    table_schema: &TableSchema,
+    embed_model: &str,
+) -> Result<()> {
+    let table_name = table_schema.get_table_name();
+    if let Some(model) = table_embed_model(table).await? {
+        if model != embed_model {
+            return Err(anyhow!(
+                "Table {} is embedded with {}, it cannot be appended with {}",
+                table_name,
+                model,
+                embed_model
+            ));
+        }
+        return Ok(());
+    }
+
+    let rows = table
+        .count_rows(None)
+        .await
+        .context("Failed to count table rows")?;
+    let model_rows = table
+        .count_rows(Some(
+            Predicate::eq(table_schema.model.name(), embed_model).to_string(),
+        ))
+        .await
+        .context("Failed to count the rows of the model")?;
+    if model_rows != rows {
+        return Err(anyhow!(
+            "Table {} has {} rows embedded with another model than {}, load it again without append",
+            table_name,
+            rows - model_rows,
+            embed_model
+        ));
+    }
+    Ok(())
+}
+
+/// Add keys to the schema metadata of the table, the existing keys are kept
+/// # Arguments
+/// * `table` - The table to update
+/// * `entries` - The keys and values to set
+/// # Returns
+/// * `Result<()>` - The result of the operation
+pub async fn update_schema_metadata(
+    table: &Table,
+    entries: impl IntoIterator<Item = (&str, String)>,
+) -> Result<()> {
+    let native = table
+        .as_native()
+        .context("Only local tables can record schema metadata")?;
+
+    // replace_schema_metadata replaces all keys so the existing keys are kept
+    let mut metadata = table.schema().await?.metadata().clone();
+    for (key, value) in entries {
+        metadata.insert(key.to_string(), value);
+    }
+    native
+        .replace_schema_metadata(metadata)
+        .await
+        .context("Failed to update schema metadata")?;
 
     Ok(())
 }
@@ -349,3 +740,93 @@ pub async fn create_inverted_index(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lancevectordb::filter::SearchFilter;
+    use crate::lancevectordb::query::{query_whole_table, QueryParams, QueryRecord};
+    use crate::lancevectordb::test_tables::temp_db;
+
+    /// Load a line of the file as the pipeline does when it loads the root directory
+    async fn load_line(
+        table_schema: &TableSchema,
+        table: &Table,
+        file_path: &str,
+        root: &str,
+    ) -> Result<()> {
+        let mut request = EmbedRequest::NewEmbedRequest(
+            "ollama",
+            "",
+            "",
+            "test-model",
+            vec!["fn main() {}"],
+            Some(0),
+        );
+        request.metadata = Some("mod.rs".to_string());
+        request.file_path = Some(file_path.to_string());
+        request.start_line = Some(1);
+        let response = EmbedResponse {
+            model: "test-model".to_string(),
+            embeddings: vec![vec![0.0; VECTOR_DB_DIM_SIZE as usize]],
+        };
+        let batch = create_record_batch(
+            Arc::new(RwLock::new(request)),
+            response,
+            table_schema,
+            "repo",
+            Path::new(root),
+        )
+        .await?;
+        insert_embeddings(table_schema, batch, table.clone()).await
+    }
+
+    async fn filtered(db: &Connection, table: &str, filter: &str) -> Result<Vec<QueryRecord>> {
+        let params = QueryParams {
+            filter: SearchFilter::parse(&[filter.to_string()])?.to_predicate(),
+            ..QueryParams::default()
+        };
+        query_whole_table(db, table, &params).await
+    }
+
+    #[tokio::test]
+    async fn path_filter_matches_the_path_in_the_loaded_directory() -> Result<()> {
+        let mut db = temp_db("path-filter").await?;
+        let table_schema = TableSchema::new(&"paths".to_string());
+        create_lance_table(&mut db, &table_schema).await?;
+        let table = db.open_table("paths").execute().await?;
+        load_line(&table_schema, &table, "./repo/src/chat/mod.rs", "./repo").await?;
+
+        let records = filtered(&db, "paths", "path=src/**/*.rs").await?;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].file_path.as_deref(), Some("src/chat/mod.rs"));
+        assert!(filtered(&db, "paths", "path=chat/*.rs").await?.is_empty());
+
+        // the same file loaded from an absolute path keeps its path and id
+        load_line(
+            &table_schema,
+            &table,
+            "/work/repo/src/chat/mod.rs",
+            "/work/repo",
+        )
+        .await?;
+        assert_eq!(table.count_rows(None).await?, 1);
+        assert_eq!(
+            filtered(&db, "paths", "path=src/chat/mod.rs").await?.len(),
+            1
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn a_loaded_file_is_relative_to_itself() {
+        assert_eq!(
+            relative_path("./src/main.rs", Path::new("./src/main.rs")),
+            "main.rs"
+        );
+        assert_eq!(
+            relative_path("/repo/src/main.rs", Path::new("/repo")),
+            "src/main.rs"
+        );
+    }
+}
@@ -1,8 +1,19 @@
+pub mod filter;
 pub mod load_lancedb;
+pub mod optimize;
+pub mod predicate;
 pub mod query;
+pub mod registry;
+pub mod tables;
+#[cfg(test)]
+pub(crate) mod test_tables;
+pub mod versions;
+use crate::app::config::AppConfig;
+use crate::app::constants::{EMBED_MODEL_KEY, OBJECT_STORE_SCHEMES};
 use crate::docsplitter::code_loader;
 use crate::docsplitter::code_loader::chunk_embed_request_arc;
 use crate::embedder::fetch_embedding;
+use ::anyhow::anyhow;
 use ::anyhow::Context;
 use ::anyhow::Result;
 use http_body_util::Full;
@@ -15,10 +26,11 @@ use load_lancedb::TableSchema;
 // use hyper::Client;
 use ::log::debug;
 use ::log::info;
+use ::log::warn;
 use ::std::path::PathBuf;
 pub type HttpsClient = LegacyClient<HttpsConnector<HttpConnector>, Full<Bytes>>;
 
-fn get_file_name(root_dir: &str) -> String {
+pub(crate) fn get_file_name(root_dir: &str) -> String {
     let root_path = PathBuf::from(root_dir);
 
     let file_name = root_path.file_name().map_or_else(
@@ -29,11 +41,86 @@ fn get_file_name(root_dir: &str) -> String {
     file_name
 }
 
+/// LoadTarget is the database and table a directory is loaded into
+#[derive(Debug, Clone)]
+pub struct LoadTarget {
+    pub database: String,
+    pub table: String,
+    /// The name stored in the source column of the loaded rows
+    pub source: String,
+    /// Add the source to the table instead of recreating the table
+    pub append: bool,
+}
+
+impl LoadTarget {
+    /// The database and table default to `<dir name>_db` and `<dir name>_table`
+    /// and the source defaults to the directory name
+    pub fn new(
+        path: &str,
+        database: Option<String>,
+        table: Option<String>,
+        source: Option<String>,
+        append: bool,
+    ) -> LoadTarget {
+        let file_name = get_file_name(path);
+        LoadTarget {
+            database: database.unwrap_or_else(|| format!("{}_{}", &file_name, "db")),
+            table: table.unwrap_or_else(|| format!("{}_{}", &file_name, "table")),
+            source: source.unwrap_or(file_name),
+            append,
+        }
+    }
+}
+
+/// Connect to a local or object store database
+/// `s3://`, `gs://` and `az://` URIs use the storage options of the config file, the standard
+/// AWS_, GOOGLE_ and AZURE_ environment variables like AWS_ENDPOINT for MinIO are read by Lance
+/// # Arguments
+/// * `uri` - The database path or URI
+/// # Returns
+/// * `Result<Connection>` - The database connection
+pub async fn connect(uri: &str) -> Result<lancedb::Connection> {
+    if let Some((scheme, _)) = uri.split_once("://") {
+        if !OBJECT_STORE_SCHEMES.contains(&scheme) {
+            return Err(anyhow!(
+                "Unsupported database URI scheme {}, use a local path or one of {}",
+                scheme,
+                OBJECT_STORE_SCHEMES.join(", ")
+            ));
+        }
+    }
+
+    let storage_options = AppConfig::load()
+        .context("Failed to load config")?
+        .storage_options(uri)?;
+    debug!(
+        "Connecting to {} with storage options {:?}",
+        uri,
+        storage_options.keys().collect::<Vec<_>>()
+    );
+
+    lancedb::connect(uri)
+        .storage_options(storage_options)
+        .execute()
+        .await
+        .with_context(|| format!("Failed to connect to the database {}", uri))
+}
+
+/// The location of the database, local paths are made absolute
+pub fn database_location(uri: &str) -> String {
+    if uri.contains("://") {
+        return uri.to_string();
+    }
+    std::path::absolute(uri)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| uri.to_string())
+}
+
 /// Run the LanceVectorDB pipeline
 /// 1. Load the codebase into chunks
 /// 2. Extract the embed requests from the chunks
 /// 3. Initialize the database
-/// 4. Create a table
+/// 4. Create a table, or open it in append mode
 /// 5. Load embeddings
 /// 6. Create an index
 /// # Arguments
@@ -41,8 +128,11 @@ fn get_file_name(root_dir: &str) -> String {
 /// * `chunk_size` - The size of the chunks
 /// * `embed_url` - The URL of the embedding API
 /// * `http_client` - The HTTP client
+/// * `distance_type` - The distance metric of the vector index
+/// * `index_params` - The kind and size of the vector index
+/// * `target` - The database and table to load into
 /// # Returns
-/// * `Result<()>` - The result of the operation
+/// * `Result<usize>` - The number of loaded chunks
 pub async fn run_embedding_pipeline(
     path: &String,
     chunk_size: usize,
@@ -51,7 +141,10 @@ pub async fn run_embedding_pipeline(
     api_key: &str,
     model: &str,
     https_client: &HttpsClient,
-) -> Result<()> {
+    distance_type: lancedb::DistanceType,
+    index_params: &load_lancedb::IndexParams,
+    target: &LoadTarget,
+) -> Result<usize> {
     // Load the codebase into chunks
     let chunks = code_loader::load_codebase_into_chunks(&path, chunk_size)
         .await
@@ -70,20 +163,24 @@ pub async fn run_embedding_pipeline(
     }
 
     // Initialize the database
-    let file_name = get_file_name(&path);
-    let db_uri = format!("{}_{}", &file_name, "db");
-    let mut db = lancedb::connect(&db_uri)
-        .execute()
+    let mut db = connect(&target.database)
         .await
         .context("Failed to connect to the database")?;
 
-    // Create table
-    let table_name = format!("{}_{}", &file_name, "table");
+    // Create table, an appended table keeps the rows of the other sources
+    let table_name = target.table.clone();
     let table_schema = TableSchema::new(&table_name);
 
-    load_lancedb::create_lance_table(&mut db, &table_schema)
-        .await
-        .context("Failed to create table")?;
+    if target.append {
+        load_lancedb::prepare_append(&mut db, &table_schema, &target.source, distance_type, model)
+            .await
+            .context("Failed to open table for append")?;
+    } else {
+        load_lancedb::create_lance_table(&mut db, &table_schema)
+            .await
+            .context("Failed to create table")?;
+    }
+    let chunk_count = embed_requests.len();
 
     // Load embeddings in parallel to improve performance
     let mut tasks = Vec::new();
@@ -92,11 +189,17 @@ pub async fn run_embedding_pipeline(
         .execute()
         .await
         .context("Failed to open table")?;
-    for (id, embed_request) in embed_requests.into_iter().enumerate() {
+    // queries and appends with another model are refused
+    load_lancedb::update_schema_metadata(&table, [(EMBED_MODEL_KEY, model.to_string())])
+        .await
+        .context("Failed to record the embedding model")?;
+    for embed_request in embed_requests {
         // let embed_url = embed_url.to_string();
         let https_client = https_client.clone();
         let table_schema = table_schema.clone();
         let table = table.clone();
+        let source = target.source.clone();
+        let root = path.clone();
 
         // Spawn a task to fetch and insert embeddings in parallel
         let task = tokio::spawn(async move {
@@ -108,10 +211,11 @@ pub async fn run_embedding_pipeline(
 
             // Create record batch
             let record_batch = load_lancedb::create_record_batch(
-                id as i32,
                 embed_request,
                 embed_response,
                 &table_schema,
+                &source,
+                std::path::Path::new(&root),
             )
             .await
             .context("Failed to create record batch")?;
@@ -136,12 +240,23 @@ pub async fn run_embedding_pipeline(
             .context("Insert Task failed")?;
     }
 
+    // the indices are built once the table has rows
+    if table.count_rows(None).await? == 0 {
+        warn!(
+            "Table {} has no rows, the indices are not built",
+            table_name
+        );
+        return Ok(chunk_count);
+    }
+
     // Create an index on the embedding column
     let embedding_col = table_schema.vector.name();
     load_lancedb::create_index_on_embedding(
         &mut db,
         table_schema.name.as_str(),
         vec![embedding_col.as_str()],
+        distance_type,
+        index_params,
     )
     .await
     .context("Failed to create index")?;
@@ -169,5 +284,5 @@ pub async fn run_embedding_pipeline(
     .await
     .context("Failed to create inverted index")?;
 
-    Ok(())
+    Ok(chunk_count)
 }
@@ -0,0 +1,185 @@
+use crate::app::constants::OPTIMIZE_RETENTION_DAYS;
+use crate::lancevectordb::tables::table_size;
+use crate::lancevectordb::versions::list_tags;
+use anyhow::{Context, Result};
+use lancedb::table::{CompactionOptions, Duration, OptimizeAction, OptimizeOptions};
+use lancedb::{Connection, Table};
+use log::info;
+use std::collections::BTreeSet;
+
+/// OptimizeParams sets how long old versions are kept, tagged versions are always kept
+#[derive(Debug, Clone)]
+pub struct OptimizeParams {
+    /// Versions older than this many days are pruned
+    pub retention_days: i64,
+    /// Also delete the files of versions newer than 7 days, only safe when nothing else writes the table
+    pub delete_unverified: bool,
+}
+
+impl Default for OptimizeParams {
+    fn default() -> Self {
+        OptimizeParams {
+            retention_days: OPTIMIZE_RETENTION_DAYS,
+            delete_unverified: false,
+        }
+    }
+}
+
+/// TableFootprint is the fragments, versions and disk usage of a table at one point
+#[derive(Debug, Clone)]
+pub struct TableFootprint {
+    /// None for tables that are not opened natively
+    pub fragments: Option<usize>,
+    pub versions: usize,
+    /// None for tables that are not on the local disk
+    pub size_bytes: Option<u64>,
+}
+
+/// OptimizeReport compares the table before and after the optimization
+#[derive(Debug, Clone)]
+pub struct OptimizeReport {
+    pub name: String,
+    pub before: TableFootprint,
+    pub after: TableFootprint,
+    pub fragments_removed: usize,
+    pub fragments_added: usize,
+    pub versions_removed: u64,
+    pub bytes_removed: u64,
+    /// Tagged versions are kept whatever their age
+    pub tagged_versions: usize,
+}
+
+async fn footprint(table: &Table) -> Result<TableFootprint> {
+    let fragments = match table.as_native() {
+        Some(native) => Some(
+            native
+                .count_fragments()
+                .await
+                .context("Failed to count the table fragments")?,
+        ),
+        None => None,
+    };
+    let versions = table
+        .list_versions()
+        .await
+        .context("Failed to list the table versions")?
+        .len();
+
+    Ok(TableFootprint {
+        fragments,
+        versions,
+        size_bytes: table_size(table.dataset_uri())?,
+    })
+}
+
+/// Compact the fragments of a table, prune its old versions and add the new rows to the indices
+/// Every chunk is written with its own merge insert, so a loaded table has a fragment and a version per chunk
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table to optimize
+/// * `params` - The retention of the old versions
+/// # Returns
+/// * `Result<OptimizeReport>` - The fragments, versions and disk usage before and after
+pub async fn optimize_table(
+    db: &Connection,
+    table_name: &str,
+    params: &OptimizeParams,
+) -> Result<OptimizeReport> {
+    let table = db
+        .open_table(table_name)
+        .execute()
+        .await
+        .with_context(|| format!("Failed to open table {}", table_name))?;
+    let before = footprint(&table).await?;
+
+    let compaction = table
+        .optimize(OptimizeAction::Compact {
+            options: CompactionOptions::default(),
+            remap_options: None,
+        })
+        .await
+        .context("Failed to compact the table fragments")?
+        .compaction
+        .unwrap_or_default();
+    info!(
+        "Compacted {} fragments into {}",
+        compaction.fragments_removed, compaction.fragments_added
+    );
+
+    // Lance keeps the tagged versions, only the versions between them are pruned
+    let tagged_versions = list_tags(&table)
+        .await?
+        .into_values()
+        .collect::<BTreeSet<u64>>()
+        .len();
+    let prune = table
+        .optimize(OptimizeAction::Prune {
+            older_than: Some(Duration::days(params.retention_days)),
+            delete_unverified: Some(params.delete_unverified),
+            error_if_tagged_old_versions: Some(false),
+        })
+        .await
+        .context("Failed to prune the old table versions")?
+        .prune
+        .unwrap_or_default();
+    info!(
+        "Pruned {} versions and {} bytes",
+        prune.old_versions, prune.bytes_removed
+    );
+
+    // an index created before the rows were appended only covers its own rows
+    table
+        .optimize(OptimizeAction::Index(OptimizeOptions::default()))
+        .await
+        .context("Failed to optimize the table indices")?;
+
+    let after = footprint(&table).await?;
+
+    Ok(OptimizeReport {
+        name: table_name.to_string(),
+        before,
+        after,
+        fragments_removed: compaction.fragments_removed,
+        fragments_added: compaction.fragments_added,
+        versions_removed: prune.old_versions,
+        bytes_removed: prune.bytes_removed,
+        tagged_versions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lancevectordb::test_tables::{add_file_rows, create_file_table, temp_db};
+    use crate::lancevectordb::versions::{list_versions, open_snapshot, tag_version, Snapshot};
+
+    #[tokio::test]
+    async fn untagged_old_versions_are_pruned_and_tagged_ones_kept() -> Result<()> {
+        let db = temp_db("optimize").await?;
+        let table = create_file_table(&db, "optimize", &[("src/main.rs", 1..6)]).await?;
+        add_file_rows(&table, "src/lib.rs", 1..6).await?;
+        let tagged = tag_version(&db, "optimize", "loaded", None).await?;
+        add_file_rows(&table, "src/cli.rs", 1..6).await?;
+        let untagged = [tagged - 1, tagged + 1];
+
+        let params = OptimizeParams {
+            retention_days: 0,
+            delete_unverified: true,
+        };
+        let report = optimize_table(&db, "optimize", &params).await?;
+        assert_eq!(report.tagged_versions, 1);
+        assert!(report.versions_removed >= 2);
+
+        let versions: Vec<u64> = list_versions(&db, "optimize")
+            .await?
+            .into_iter()
+            .map(|v| v.version)
+            .collect();
+        assert!(versions.contains(&tagged));
+        assert!(untagged.iter().all(|v| !versions.contains(v)));
+
+        let loaded = open_snapshot(&db, "optimize", &Snapshot::Tag("loaded".to_string())).await?;
+        assert_eq!(loaded.count_rows(None).await?, 10);
+        Ok(())
+    }
+}
@@ -0,0 +1,369 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+/// Literal is a value compared in a predicate, strings are quoted when the SQL is written
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    String(String),
+    Int(i64),
+    Timestamp(DateTime<Utc>),
+}
+
+impl From<&str> for Literal {
+    fn from(value: &str) -> Self {
+        Literal::String(value.to_string())
+    }
+}
+
+impl From<String> for Literal {
+    fn from(value: String) -> Self {
+        Literal::String(value)
+    }
+}
+
+impl From<&String> for Literal {
+    fn from(value: &String) -> Self {
+        Literal::String(value.clone())
+    }
+}
+
+impl From<i32> for Literal {
+    fn from(value: i32) -> Self {
+        Literal::Int(value as i64)
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(value: i64) -> Self {
+        Literal::Int(value)
+    }
+}
+
+impl From<DateTime<Utc>> for Literal {
+    fn from(value: DateTime<Utc>) -> Self {
+        Literal::Timestamp(value)
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::String(value) => write!(f, "{}", quote(value)),
+            Literal::Int(value) => write!(f, "{}", value),
+            Literal::Timestamp(value) => {
+                write!(f, "timestamp '{}'", value.format("%Y-%m-%d %H:%M:%S"))
+            }
+        }
+    }
+}
+
+/// Predicate is a typed Lance SQL filter, the values are escaped when the SQL is written
+/// ```ignore
+/// let predicate = Predicate::eq("metadata", "it's.rs").and(Predicate::range("chunk_number", Some(2), None));
+/// assert_eq!(predicate.to_string(), "(metadata = 'it''s.rs') AND (chunk_number >= 2)");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Eq(String, Literal),
+    In(String, Vec<Literal>),
+    Like(String, String),
+    /// The lower bound is inclusive and the upper bound exclusive
+    Range {
+        column: String,
+        lower: Option<Literal>,
+        upper: Option<Literal>,
+    },
+    IsNull(String),
+    IsNotNull(String),
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+}
+
+impl Predicate {
+    /// The column equals the value
+    pub fn eq(column: &str, value: impl Into<Literal>) -> Predicate {
+        Predicate::Eq(column.to_string(), value.into())
+    }
+
+    /// The column is one of the values, an empty list matches nothing
+    pub fn is_in<T: Into<Literal>>(column: &str, values: impl IntoIterator<Item = T>) -> Predicate {
+        Predicate::In(
+            column.to_string(),
+            values.into_iter().map(Into::into).collect(),
+        )
+    }
+
+    /// The column matches the LIKE pattern, `%` matches any characters and `_` one
+    pub fn like(column: &str, pattern: &str) -> Predicate {
+        Predicate::Like(column.to_string(), pattern.to_string())
+    }
+
+    /// The column is at least the lower bound and below the upper bound
+    pub fn range<T: Into<Literal>>(column: &str, lower: Option<T>, upper: Option<T>) -> Predicate {
+        Predicate::Range {
+            column: column.to_string(),
+            lower: lower.map(Into::into),
+            upper: upper.map(Into::into),
+        }
+    }
+
+    pub fn is_null(column: &str) -> Predicate {
+        Predicate::IsNull(column.to_string())
+    }
+
+    pub fn is_not_null(column: &str) -> Predicate {
+        Predicate::IsNotNull(column.to_string())
+    }
+
+    /// Both predicates match, nested ands are flattened
+    pub fn and(self, other: Predicate) -> Predicate {
+        match self {
+            Predicate::And(mut predicates) => {
+                predicates.push(other);
+                Predicate::And(predicates)
+            }
+            predicate => Predicate::And(vec![predicate, other]),
+        }
+    }
+
+    /// Either predicate matches, nested ors are flattened
+    pub fn or(self, other: Predicate) -> Predicate {
+        match self {
+            Predicate::Or(mut predicates) => {
+                predicates.push(other);
+                Predicate::Or(predicates)
+            }
+            predicate => Predicate::Or(vec![predicate, other]),
+        }
+    }
+
+    /// All the predicates match, None if there are no predicates
+    pub fn all(predicates: impl IntoIterator<Item = Predicate>) -> Option<Predicate> {
+        predicates.into_iter().reduce(Predicate::and)
+    }
+
+    /// The columns the predicate reads, in the order they appear
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Predicate::Eq(column, _)
+            | Predicate::In(column, _)
+            | Predicate::Like(column, _)
+            | Predicate::Range { column, .. }
+            | Predicate::IsNull(column)
+            | Predicate::IsNotNull(column) => vec![column.as_str()],
+            Predicate::And(predicates) | Predicate::Or(predicates) => {
+                predicates.iter().flat_map(|p| p.columns()).collect()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Predicate::Eq(column, value) => write!(f, "{} = {}", identifier(column), value),
+            Predicate::In(_, values) if values.is_empty() => write!(f, "FALSE"),
+            Predicate::In(column, values) => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "{} IN ({})", identifier(column), values.join(", "))
+            }
+            Predicate::Like(column, pattern) => {
+                write!(f, "{} LIKE {}", identifier(column), quote(pattern))
+            }
+            Predicate::Range {
+                column,
+                lower,
+                upper,
+            } => match (lower, upper) {
+                (Some(lower), Some(upper)) => write!(
+                    f,
+                    "{column} >= {} AND {column} < {}",
+                    lower,
+                    upper,
+                    column = identifier(column)
+                ),
+                (Some(lower), None) => write!(f, "{} >= {}", identifier(column), lower),
+                (None, Some(upper)) => write!(f, "{} < {}", identifier(column), upper),
+                (None, None) => write!(f, "TRUE"),
+            },
+            Predicate::IsNull(column) => write!(f, "{} IS NULL", identifier(column)),
+            Predicate::IsNotNull(column) => write!(f, "{} IS NOT NULL", identifier(column)),
+            Predicate::And(predicates) => join(f, predicates, " AND ", "TRUE"),
+            Predicate::Or(predicates) => join(f, predicates, " OR ", "FALSE"),
+        }
+    }
+}
+
+fn join(
+    f: &mut fmt::Formatter<'_>,
+    predicates: &[Predicate],
+    separator: &str,
+    empty: &str,
+) -> fmt::Result {
+    if predicates.is_empty() {
+        return write!(f, "{}", empty);
+    }
+    let predicates: Vec<String> = predicates.iter().map(|p| format!("({})", p)).collect();
+    write!(f, "{}", predicates.join(separator))
+}
+
+/// Quote a string literal, quotes inside the value are doubled so `it's.rs` becomes `'it''s.rs'`
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Column names other than plain identifiers are quoted with backticks
+fn identifier(column: &str) -> String {
+    let plain = column
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && column
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        column.to_string()
+    } else {
+        format!("`{}`", column.replace('`', "``"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn quotes_inside_strings_are_doubled() {
+        let predicate = Predicate::eq("metadata", "it's.rs");
+        assert_eq!(predicate.to_string(), "metadata = 'it''s.rs'");
+    }
+
+    #[test]
+    fn injected_conditions_stay_inside_the_literal() {
+        let predicate = Predicate::eq("metadata", "x' OR '1'='1");
+        assert_eq!(predicate.to_string(), "metadata = 'x'' OR ''1''=''1'");
+
+        let predicate = Predicate::eq("metadata", "a'); DROP TABLE t; --");
+        assert_eq!(predicate.to_string(), "metadata = 'a''); DROP TABLE t; --'");
+    }
+
+    #[test]
+    fn in_list_escapes_every_value() {
+        let predicate = Predicate::is_in("metadata", ["main.rs", "it's.rs", "''"]);
+        assert_eq!(
+            predicate.to_string(),
+            "metadata IN ('main.rs', 'it''s.rs', '''''')"
+        );
+    }
+
+    #[test]
+    fn empty_in_list_matches_nothing() {
+        let predicate = Predicate::is_in("metadata", Vec::<String>::new());
+        assert_eq!(predicate.to_string(), "FALSE");
+    }
+
+    #[test]
+    fn integers_are_not_quoted() {
+        let predicate = Predicate::is_in("chunk_number", [1, -2, i32::MAX]);
+        assert_eq!(predicate.to_string(), "chunk_number IN (1, -2, 2147483647)");
+    }
+
+    #[test]
+    fn like_pattern_is_quoted() {
+        let predicate = Predicate::like("file_path", "%/o'brien/%.rs");
+        assert_eq!(predicate.to_string(), "file_path LIKE '%/o''brien/%.rs'");
+    }
+
+    #[test]
+    fn range_bounds() {
+        assert_eq!(
+            Predicate::range("chunk_number", Some(2), Some(5)).to_string(),
+            "chunk_number >= 2 AND chunk_number < 5"
+        );
+        assert_eq!(
+            Predicate::range("chunk_number", Some(2), None).to_string(),
+            "chunk_number >= 2"
+        );
+        assert_eq!(
+            Predicate::range("chunk_number", None, Some(5)).to_string(),
+            "chunk_number < 5"
+        );
+        assert_eq!(
+            Predicate::range::<i32>("chunk_number", None, None).to_string(),
+            "TRUE"
+        );
+    }
+
+    #[test]
+    fn timestamps_are_typed_literals() {
+        let time = Utc.with_ymd_and_hms(2024, 6, 1, 12, 30, 0).unwrap();
+        let predicate = Predicate::range("created_at", Some(time), None);
+        assert_eq!(
+            predicate.to_string(),
+            "created_at >= timestamp '2024-06-01 12:30:00'"
+        );
+    }
+
+    #[test]
+    fn and_or_are_parenthesized_and_flattened() {
+        let predicate = Predicate::is_not_null("content")
+            .and(Predicate::eq("language", "rust"))
+            .and(Predicate::eq("symbol_kind", "function").or(Predicate::eq("symbol_kind", "impl")));
+        assert_eq!(
+            predicate.to_string(),
+            "(content IS NOT NULL) AND (language = 'rust') AND ((symbol_kind = 'function') OR (symbol_kind = 'impl'))"
+        );
+    }
+
+    #[test]
+    fn file_path_or_file_name() {
+        let predicate = Predicate::eq("file_path", "src/mod.rs")
+            .or(Predicate::is_null("file_path").and(Predicate::eq("metadata", "src/mod.rs")));
+        assert_eq!(
+            predicate.to_string(),
+            "(file_path = 'src/mod.rs') OR ((file_path IS NULL) AND (metadata = 'src/mod.rs'))"
+        );
+    }
+
+    #[test]
+    fn columns_of_nested_predicates() {
+        let predicate = Predicate::eq("language", "rust")
+            .and(Predicate::eq("symbol_kind", "impl").or(Predicate::is_null("file_path")));
+        assert_eq!(
+            predicate.columns(),
+            vec!["language", "symbol_kind", "file_path"]
+        );
+    }
+
+    #[test]
+    fn all_of_nothing_is_none() {
+        assert_eq!(Predicate::all(Vec::new()), None);
+        assert_eq!(
+            Predicate::all(vec![Predicate::is_not_null("content")]),
+            Some(Predicate::is_not_null("content"))
+        );
+    }
+
+    #[test]
+    fn unusual_column_names_are_quoted() {
+        assert_eq!(
+            Predicate::is_not_null("my column").to_string(),
+            "`my column` IS NOT NULL"
+        );
+        assert_eq!(
+            Predicate::is_not_null("a`b").to_string(),
+            "`a``b` IS NOT NULL"
+        );
+        assert_eq!(
+            Predicate::is_not_null("_distance").to_string(),
+            "_distance IS NOT NULL"
+        );
+    }
+
+    #[test]
+    fn unicode_and_control_characters_stay_inside_the_literal() {
+        let predicate = Predicate::eq("metadata", "naïve\n'😀'.rs");
+        assert_eq!(predicate.to_string(), "metadata = 'naïve\n''😀''.rs'");
+    }
+}
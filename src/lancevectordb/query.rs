@@ -1,22 +1,128 @@
+use crate::app::constants::{
+    DISTANCE_TYPE_KEY, EMBED_MODEL_KEY, QUERY_CONCURRENCY, QUERY_LIMIT, QUERY_NPROBES,
+    QUERY_REFINE_FACTOR, RRF_K, VECTOR_INDEX_KEY,
+};
 use crate::embedder;
 use crate::embedder::config::EmbedRequest;
 // use hyper::client::HttpConnector;
 // use ::hyper::Client as HttpClient;
+use crate::lancevectordb::predicate::Predicate;
+use crate::lancevectordb::versions::{open_snapshot, Snapshot};
 use crate::lancevectordb::HttpsClient;
 use anyhow::{anyhow, Context, Result};
-use arrow_array::{Array, StringArray};
+use arrow_array::{Array, Float32Array, StringArray};
 use arrow_array::{Int32Array, RecordBatch};
 use arrow_schema::DataType::{Int32, Utf8};
 use arrow_schema::SchemaRef;
 use futures::StreamExt;
 use lancedb::arrow::SendableRecordBatchStream;
+use lancedb::index::scalar::FullTextSearchQuery;
 use lancedb::query::ExecutableQuery;
 use lancedb::query::IntoQueryVector;
 use lancedb::query::QueryBase;
-use lancedb::{Connection, Table};
-use log::{debug, error};
+use lancedb::{Connection, DistanceType, Table};
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+
+/// QueryRecord is a row returned from the vector table
+/// distance is only set for nearest vector queries, rerank_score once the rows are reranked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryRecord {
+    /// The stable id of the row, None for tables loaded before rows had stable ids
+    #[serde(default)]
+    pub id: Option<String>,
+    pub content: String,
+    pub metadata: String,
+    pub chunk_number: Option<i32>,
+    pub distance: Option<f32>,
+    /// The similarity of the distance in the metric of the table, higher is nearer
+    #[serde(default)]
+    pub similarity: Option<f32>,
+    pub file_path: Option<String>,
+    pub start_line: Option<i32>,
+    pub end_line: Option<i32>,
+    #[serde(default)]
+    pub rerank_score: Option<f32>,
+}
+
+impl QueryRecord {
+    /// Path of the source file, the file name for tables loaded without paths
+    pub fn path(&self) -> &str {
+        self.file_path.as_deref().unwrap_or(&self.metadata)
+    }
+
+    /// Score of the record, the rerank score if reranked
+    /// otherwise the similarity of the distance of the nearest vector query
+    pub fn score(&self) -> Option<f32> {
+        self.rerank_score.or(self.similarity)
+    }
+}
+
+/// The similarity of a distance, the cosine and dot distances are one minus the similarity
+/// while the L2 distance is unbounded and mapped into 0 to 1
+/// # Arguments
+/// * `distance` - The distance returned by the nearest vector query
+/// * `distance_type` - The metric the distance was measured with
+/// # Returns
+/// * `f32` - The similarity, higher is nearer
+pub fn similarity(distance: f32, distance_type: DistanceType) -> f32 {
+    match distance_type {
+        DistanceType::Cosine | DistanceType::Dot => 1.0 - distance,
+        _ => 1.0 / (1.0 + distance.max(0.0)),
+    }
+}
+
+/// QueryParams tunes the nearest vector search
+#[derive(Debug, Clone)]
+pub struct QueryParams {
+    /// Number of rows returned by the vector search
+    pub limit: usize,
+    /// Number of index partitions searched
+    pub nprobes: usize,
+    /// Multiplier of the rows re-scored with the full vectors
+    pub refine_factor: u32,
+    /// Distance metric requested on the command line, it has to match the metric of the index
+    pub distance_type: Option<DistanceType>,
+    /// Rows with a larger distance are dropped after the query
+    pub max_distance: Option<f32>,
+    /// Search the full-text index of the content instead of the vectors
+    pub full_text: bool,
+    /// Predicate applied before the search
+    pub filter: Option<Predicate>,
+    /// Rows skipped before the first returned row
+    pub offset: usize,
+    /// Rows returned by a whole table query, all remaining rows if None
+    pub page_size: Option<usize>,
+    /// The table version to search
+    pub snapshot: Snapshot,
+}
+
+impl Default for QueryParams {
+    fn default() -> Self {
+        QueryParams {
+            limit: QUERY_LIMIT,
+            nprobes: QUERY_NPROBES,
+            refine_factor: QUERY_REFINE_FACTOR,
+            distance_type: None,
+            max_distance: None,
+            full_text: false,
+            filter: None,
+            offset: 0,
+            page_size: None,
+            snapshot: Snapshot::Latest,
+        }
+    }
+}
+
+/// QueryResult holds the rows found for one query input
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryResult {
+    pub query: String,
+    pub records: Vec<QueryRecord>,
+}
 
 /// Run the query to get the nearest embeddings
+/// Every input is searched, the results of several inputs are fused into one ranking
 /// Arguments:
 /// - rt: &tokio::runtime::Runtime
 /// - embed_model: String
@@ -25,8 +131,9 @@ use log::{debug, error};
 /// - db_config: VectorDbConfig
 /// - http_client: &HttpClient<HttpConnector>
 /// - whole_query: bool
+/// - params: &QueryParams
 /// Returns:
-/// - Result<Vec<String>>
+/// - Result<Vec<QueryRecord>>
 pub async fn run_query(
     db: &mut Connection,
     provider: &str,
@@ -38,18 +145,96 @@ pub async fn run_query(
     http_client: &HttpsClient,
     whole_query: bool,
     file_context: bool,
-) -> Result<Vec<String>> {
+    params: &QueryParams,
+) -> Result<Vec<QueryRecord>> {
+    let mut results = run_queries(
+        db,
+        provider,
+        api_url,
+        api_key,
+        embed_model,
+        input_list,
+        vector_table,
+        http_client,
+        whole_query,
+        file_context,
+        params,
+    )
+    .await?;
+
+    if results.len() == 1 {
+        return Ok(results.remove(0).records);
+    }
+    Ok(fuse_results(&results))
+}
+
+/// Embed every input and search the table for each of them concurrently
+/// # Arguments
+/// * `db` - The database connection
+/// * `provider` - The embedding provider
+/// * `api_url` - The URL of the embedding API
+/// * `api_key` - The API key of the embedding API
+/// * `embed_model` - The embedding model
+/// * `input_list` - The query inputs, embedded in one request
+/// * `vector_table` - The table to search
+/// * `http_client` - The HTTP client to use for requests
+/// * `whole_query` - If true, fetches all content from the table once for all inputs without embedding them
+/// * `file_context` - If true, fetches the entire file context for the nearest vectors
+/// * `params` - The search parameters of the nearest vector query
+/// # Returns
+/// * `Result<Vec<QueryResult>>` - The rows found for every input in input order
+pub async fn run_queries(
+    db: &mut Connection,
+    provider: &str,
+    api_url: &str,
+    api_key: &str,
+    embed_model: &str,
+    input_list: &[String],
+    vector_table: &str,
+    http_client: &HttpsClient,
+    whole_query: bool,
+    file_context: bool,
+    params: &QueryParams,
+) -> Result<Vec<QueryResult>> {
     // colog::init();
 
     debug!("Starting query");
 
     // let commands = build_args();
-    debug!("Length of input list: {}", input_list[0].len());
-    // check if list is length one String is length one
-    if input_list.len() == 1 && input_list[0].is_empty() {
+    debug!("Length of input list: {}", input_list.len());
+    // check if the list is empty or has only empty inputs
+    if input_list.iter().all(|input| input.trim().is_empty()) {
         error!("Query Input is empty");
         return Err(anyhow!("Query Input is empty"));
     }
+    let input_list: Vec<String> = input_list
+        .iter()
+        .filter(|input| !input.trim().is_empty())
+        .cloned()
+        .collect();
+
+    // the whole table does not depend on the query
+    if whole_query {
+        let records = query_whole_table(db, vector_table, params)
+            .await
+            .context("Failed to query table")?;
+        return Ok(vec![QueryResult {
+            query: input_list.join("\n"),
+            records,
+        }]);
+    }
+
+    // the full-text search does not need the embeddings
+    if params.full_text {
+        let db = &*db;
+        let searches = input_list.into_iter().map(|query| async move {
+            let records = query_full_text_table(db, vector_table, &query, file_context, params)
+                .await
+                .with_context(|| format!("Failed to search table for {}", query))?;
+            Ok(QueryResult { query, records })
+        });
+        return collect_results(searches).await;
+    }
 
     // let url = format!("{}/{}", CHAT_API_URL, "api/embed");
 
@@ -59,7 +244,7 @@ pub async fn run_query(
         api_url,
         api_key,
         embed_model,
-        input_list,
+        &input_list,
         &"".to_string(),
         None,
     );
@@ -70,16 +255,88 @@ pub async fn run_query(
         .await
         .with_context(|| format!("Failed to fetch embedding response from {}", &embed_url))?;
 
-    let query_vector = query_response.embeddings[0].clone();
+    if query_response.embeddings.len() != input_list.len() {
+        return Err(anyhow!(
+            "Expected {} query embeddings, got {}",
+            input_list.len(),
+            query_response.embeddings.len()
+        ));
+    }
 
-    // query the vector table
-    let content = query_vector_table(db, vector_table, query_vector, whole_query, file_context)
-        .await
-        .context("Failed to query table")?;
+    // query the vector table for every input
+    let searches =
+        input_list
+            .into_iter()
+            .zip(query_response.embeddings)
+            .map(|(query, query_vector)| {
+                let mut db = db.clone();
+                async move {
+                    let records = query_vector_table(
+                        &mut db,
+                        vector_table,
+                        query_vector,
+                        embed_model,
+                        whole_query,
+                        file_context,
+                        params,
+                    )
+                    .await
+                    .with_context(|| format!("Failed to query table for {}", query))?;
+                    Ok(QueryResult { query, records })
+                }
+            });
+    let results = collect_results(searches).await?;
 
     debug!("Finishes running query");
 
-    Ok(content)
+    Ok(results)
+}
+
+/// Run the searches concurrently keeping the input order
+async fn collect_results(
+    searches: impl Iterator<Item = impl std::future::Future<Output = Result<QueryResult>>>,
+) -> Result<Vec<QueryResult>> {
+    futures::stream::iter(searches)
+        .buffered(QUERY_CONCURRENCY)
+        .collect::<Vec<Result<QueryResult>>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// Fuse the results of several queries into one ranking with reciprocal rank fusion
+/// A row found by several queries ranks higher and keeps its smallest distance
+/// # Arguments
+/// * `results` - The results of every query, each ranked nearest first
+/// # Returns
+/// * `Vec<QueryRecord>` - The distinct rows ordered by the fused rank
+pub fn fuse_results(results: &[QueryResult]) -> Vec<QueryRecord> {
+    let mut fused: Vec<(f32, QueryRecord)> = Vec::new();
+    for result in results {
+        for (rank, record) in result.records.iter().enumerate() {
+            let rrf = 1.0 / (RRF_K + rank as f32 + 1.0);
+            let existing = fused.iter_mut().find(|(_, r)| {
+                r.path() == record.path()
+                    && r.chunk_number == record.chunk_number
+                    && r.start_line == record.start_line
+                    && r.content == record.content
+            });
+            match existing {
+                Some((score, existing)) => {
+                    *score += rrf;
+                    if record.distance.unwrap_or(f32::MAX) < existing.distance.unwrap_or(f32::MAX) {
+                        existing.distance = record.distance;
+                        existing.similarity = record.similarity;
+                    }
+                }
+                None => fused.push((rrf, record.clone())),
+            }
+        }
+    }
+
+    // stable sort keeps the query order for equal ranks
+    fused.sort_by(|a, b| b.0.total_cmp(&a.0));
+    fused.into_iter().map(|(_, record)| record).collect()
 }
 
 /// Queries a vector table in the database, either fetching all content or querying the nearest vectors.
@@ -88,87 +345,250 @@ pub async fn run_query(
 /// * `db` - A mutable reference to the database connection.
 /// * `table_name` - The name of the table to query.
 /// * `query_vector` - The vector to query against the table.
+/// * `embed_model` - The model the query vector is embedded with, it has to match the model of the table.
 /// * `whole_query` - If true, fetches all content from the table. If false, queries the nearest vectors.
 /// * `file_context` - If true, fetches the entire file context for the nearest vectors.
+/// * `params` - The search parameters of the nearest vector query.
 ///
 /// # Returns
-/// A `Result` containing a vector of strings representing the queried content, or an error if the operation fails.
+/// A `Result` containing the queried records, or an error if the operation fails.
 pub async fn query_vector_table(
     db: &mut Connection,
     table_name: &str,
     query_vector: impl IntoQueryVector,
+    embed_model: &str,
     whole_query: bool,
     file_context: bool,
-) -> Result<Vec<String>> {
-    let table = db
-        .open_table(table_name)
+    params: &QueryParams,
+) -> Result<Vec<QueryRecord>> {
+    let table = open_snapshot(db, table_name, &params.snapshot).await?;
+
+    if whole_query {
+        query_all_content(&table, params).await
+    } else {
+        let (stream, distance_type) =
+            query_nearest_vector(query_vector, embed_model, &table, params).await?;
+        let mut content = collect_records(stream)
+            .await
+            .context("Failed to get records from record batch")?;
+        for record in content.iter_mut() {
+            record.similarity = record.distance.map(|d| similarity(d, distance_type));
+        }
+
+        // distance_range hits a DataFusion bug so the cutoff is applied after the query
+        if let Some(max_distance) = params.max_distance {
+            content.retain(|r| r.distance.is_some_and(|d| d <= max_distance));
+            debug!(
+                "{} records within the max distance {}",
+                content.len(),
+                max_distance
+            );
+        }
+
+        match file_context {
+            true => query_file_context(&table, content).await,
+            false => Ok(content),
+        }
+    }
+}
+
+/// Search the full-text index of the content column, the filter is applied before the search
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table to query
+/// * `text` - The text to search for
+/// * `file_context` - If true, fetches the entire file context for the matching rows
+/// * `params` - The search parameters, the limit and filter are used
+/// # Returns
+/// * `Result<Vec<QueryRecord>>` - The matching rows, best match first
+pub async fn query_full_text_table(
+    db: &Connection,
+    table_name: &str,
+    text: &str,
+    file_context: bool,
+    params: &QueryParams,
+) -> Result<Vec<QueryRecord>> {
+    let table = open_snapshot(db, table_name, &params.snapshot).await?;
+    let filter = with_filter(Predicate::is_not_null("content"), params);
+    let columns = record_columns(&table, &filter).await?;
+
+    let stream = table
+        .query()
+        .full_text_search(
+            FullTextSearchQuery::new(text.to_string()).columns(Some(vec!["content".to_string()])),
+        )
+        .only_if(filter.to_string())
+        .select(lancedb::query::Select::Columns(columns))
+        .limit(params.limit)
+        .offset(params.offset)
         .execute()
         .await
-        .context("Failed to open a table")?;
+        .context("Failed to execute full text query and fetch records")?;
 
-    let batches;
+    let content = collect_records(stream)
+        .await
+        .context("Failed to get records from record batch")?;
 
-    if whole_query {
-        let stream = query_all_content(&table).await?;
-        batches = stream.collect::<Vec<_>>().await;
-        let content = get_content_from_stream(&batches, "content")
-            .context("Failed to get content from record batch")?;
+    match file_context {
+        true => query_file_context(&table, content).await,
+        false => Ok(content),
+    }
+}
 
-        Ok(content)
-    } else {
-        let stream = query_nearest_vector(query_vector, &table).await?;
-        batches = stream.collect::<Vec<_>>().await;
-        match file_context {
-            true => {
-                // Fetch the whole file context
-                let files = get_content_from_stream(&batches, "metadata")
-                    .context("Failed to get file names from metadata")?;
-
-                // files remove duplicates
-                let files_unique: Vec<String> = files
-                    .into_iter()
-                    .filter(|x| x != "NULL")
-                    .collect::<std::collections::HashSet<_>>()
-                    .into_iter()
-                    .collect();
-
-                debug!("Unique file names after deduplication: {:?}", &files_unique);
-
-                // query the content based on file names
-                let file_content = query_content_based_on_metadata(&table, files_unique)
-                    .await
-                    .context("Failed to query content based on file name metadata")?;
-
-                // convert the stream to a vector
-                let file_batch = file_content.collect::<Vec<_>>().await;
-                // debug!("File Batch: {:?}", &file_batch); // add if required
-                // get the content from the record batch
-                let file_data = get_content_from_stream(&file_batch, "content")
-                    .context("Failed to get content from record batch")?;
-                // debug!("Chunk Data: {:?}", &file_data); // add if required
-                Ok(file_data)
-            }
-            false => {
-                debug!("Number of batches retrieved from query: {}", &batches.len());
-                let content = get_content_from_stream(&batches, "content")
-                    .context("Failed to get content from record batch")?;
+/// Fetch all the rows of the files the records belong to
+async fn query_file_context(table: &Table, content: Vec<QueryRecord>) -> Result<Vec<QueryRecord>> {
+    // Fetch the whole file context, files remove duplicates
+    let files_unique: Vec<String> = content
+        .into_iter()
+        .map(|r| r.metadata)
+        .filter(|x| x != "NULL")
+        .collect::<std::collections::HashSet<_>>()
+        .into_iter()
+        .collect();
 
-                Ok(content)
-            }
+    debug!("Unique file names after deduplication: {:?}", &files_unique);
+
+    // query the content based on file names
+    let file_content = query_content_based_on_metadata(table, files_unique)
+        .await
+        .context("Failed to query content based on file name metadata")?;
+
+    // get the records from every record batch of the stream
+    let file_data = collect_records(file_content)
+        .await
+        .context("Failed to get records from record batch")?;
+    // debug!("Chunk Data: {:?}", &file_data); // add if required
+    Ok(file_data)
+}
+
+/// Add the user filter of the query parameters to the predicate
+fn with_filter(predicate: Predicate, params: &QueryParams) -> Predicate {
+    match &params.filter {
+        Some(filter) => predicate.and(filter.clone()),
+        None => predicate,
+    }
+}
+
+/// Read the chunks of an indexed file in chunk order
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table to query
+/// * `file_name` - The file name stored in the metadata column
+/// * `start_chunk` - The first chunk to read
+/// * `end_chunk` - The last chunk to read, all remaining chunks if None
+/// * `snapshot` - The table version to read
+/// # Returns
+/// * `Result<Vec<QueryRecord>>` - The chunks of the file
+pub async fn query_file_chunks(
+    db: &Connection,
+    table_name: &str,
+    file_name: &str,
+    start_chunk: i32,
+    end_chunk: Option<i32>,
+    snapshot: &Snapshot,
+) -> Result<Vec<QueryRecord>> {
+    let table = open_snapshot(db, table_name, snapshot).await?;
+    let schema = table.schema().await.context("Failed to get table schema")?;
+
+    // the end chunk is included
+    let filter = file_predicate(&schema, file_name).and(Predicate::range(
+        "chunk_number",
+        Some(start_chunk),
+        end_chunk.map(|end| end.saturating_add(1)),
+    ));
+    let columns = record_columns(&table, &filter).await?;
+
+    let stream = table
+        .query()
+        .only_if(filter.to_string())
+        .select(lancedb::query::Select::Columns(columns))
+        .limit(scan_limit(&table).await?)
+        .execute()
+        .await
+        .context("Failed to execute file chunk query and fetch records")?;
+
+    let mut records = collect_records(stream)
+        .await
+        .context("Failed to get records from record batch")?;
+    records.sort_by_key(|r| (r.chunk_number, r.start_line));
+
+    Ok(records)
+}
+
+/// Match the rows of a file by its path, the file name of tables loaded without paths
+/// Matching the file name alone would merge every `mod.rs` of the repository
+fn file_predicate(schema: &SchemaRef, path: &str) -> Predicate {
+    if schema.field_with_name("file_path").is_err() {
+        return Predicate::eq("metadata", path);
+    }
+    Predicate::eq("file_path", path)
+        .or(Predicate::is_null("file_path").and(Predicate::eq("metadata", path)))
+}
+
+/// List the distinct file paths, the file names stored in the metadata column for rows without a path
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table to query
+/// * `snapshot` - The table version to read
+/// # Returns
+/// * `Result<Vec<String>>` - The sorted file paths
+pub async fn list_files(
+    db: &Connection,
+    table_name: &str,
+    snapshot: &Snapshot,
+) -> Result<Vec<String>> {
+    let table = open_snapshot(db, table_name, snapshot).await?;
+    let schema = table.schema().await.context("Failed to get table schema")?;
+    let mut columns = vec!["metadata".to_string()];
+    if schema.field_with_name("file_path").is_ok() {
+        columns.push("file_path".to_string());
+    }
+
+    let mut stream = table
+        .query()
+        .select(lancedb::query::Select::Columns(columns))
+        .limit(scan_limit(&table).await?)
+        .execute()
+        .await
+        .context("Failed to execute metadata query and fetch records")?;
+
+    let mut files = std::collections::BTreeSet::new();
+    while let Some(batch) = stream.next().await {
+        let batch_ref = batch.map_err(|e| anyhow!(format!("Failed to get RecordBatch: {}", e)))?;
+        let Some(metadata) = batch_ref
+            .column_by_name("metadata")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+        else {
+            continue;
+        };
+        let file_path = batch_ref
+            .column_by_name("file_path")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+        for i in 0..batch_ref.num_rows() {
+            match file_path.filter(|f| f.is_valid(i)) {
+                Some(file_path) => files.insert(file_path.value(i).to_string()),
+                None if metadata.is_valid(i) => files.insert(metadata.value(i).to_string()),
+                None => false,
+            };
         }
     }
+
+    Ok(files.into_iter().collect())
 }
 
 /// Get content from the record stream based on the column name for example "metadata" has the file names
+/// The values of the column are read from every batch of the stream
 /// Arguments:
 /// - batches: &Vec<lancedb::error::Result<RecordBatch>>
 /// - table_column: &str
 /// Returns:
 /// - Result<Vec<String>>
+#[allow(dead_code)]
 fn get_content_from_stream(
     batches: &Vec<lancedb::error::Result<RecordBatch>>,
     table_column: &str,
 ) -> Result<Vec<String>> {
+    let mut content = Vec::new();
     for batch in batches {
         // to avoid moving the elements and instead borrow them,
         // iterate over references to the elements:
@@ -177,13 +597,81 @@ fn get_content_from_stream(
             .map_err(|e| anyhow!(format!("Failed to get RecordBatch: {}", e)))?;
         let schema = batch_ref.schema(); // Bind schema to a variable
 
-        let content = get_column_data_from_batch(table_column, batch_ref, schema);
-        if content.is_ok() {
-            return content;
-        }
+        content.extend(get_column_data_from_batch(table_column, batch_ref, schema)?);
     }
 
-    Ok(Vec::new())
+    Ok(content)
+}
+
+/// Read the records of every batch as the stream yields them
+/// # Arguments
+/// * `stream` - The record batch stream of a query
+/// # Returns
+/// * `Result<Vec<QueryRecord>>` - The records of all the batches in stream order
+async fn collect_records(mut stream: SendableRecordBatchStream) -> Result<Vec<QueryRecord>> {
+    let mut records = Vec::new();
+    let mut batches = 0;
+    while let Some(batch) = stream.next().await {
+        let batch = batch.map_err(|e| anyhow!(format!("Failed to get RecordBatch: {}", e)))?;
+        append_records(&batch, &mut records)?;
+        batches += 1;
+    }
+
+    debug!("Fetched {} records from {} batches", records.len(), batches);
+    Ok(records)
+}
+
+/// Append the records of the batch
+/// content is required, metadata, chunk_number, _distance, file_path and the line range are read when selected
+/// # Arguments
+/// * `batch_ref` - The record batch
+/// * `records` - The records the rows of the batch are appended to
+fn append_records(batch_ref: &RecordBatch, records: &mut Vec<QueryRecord>) -> Result<()> {
+    let content = batch_ref
+        .column_by_name("content")
+        .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+        .context("Failed to get content column from the batch")?;
+    let metadata = batch_ref
+        .column_by_name("metadata")
+        .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+    let chunk_number = batch_ref
+        .column_by_name("chunk_number")
+        .and_then(|c| c.as_any().downcast_ref::<Int32Array>());
+    let distance = batch_ref
+        .column_by_name("_distance")
+        .and_then(|c| c.as_any().downcast_ref::<Float32Array>());
+    let file_path = batch_ref
+        .column_by_name("file_path")
+        .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+    let start_line = batch_ref
+        .column_by_name("start_line")
+        .and_then(|c| c.as_any().downcast_ref::<Int32Array>());
+    let end_line = batch_ref
+        .column_by_name("end_line")
+        .and_then(|c| c.as_any().downcast_ref::<Int32Array>());
+    // older tables have integer ids that are not stable across loads
+    let id = batch_ref
+        .column_by_name("id")
+        .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+
+    for i in 0..batch_ref.num_rows() {
+        records.push(QueryRecord {
+            id: id.filter(|r| r.is_valid(i)).map(|r| r.value(i).to_string()),
+            content: content.value(i).to_string(),
+            metadata: metadata.map_or_else(|| "NULL".to_string(), |m| m.value(i).to_string()),
+            chunk_number: chunk_number.filter(|c| c.is_valid(i)).map(|c| c.value(i)),
+            distance: distance.filter(|d| d.is_valid(i)).map(|d| d.value(i)),
+            similarity: None,
+            file_path: file_path
+                .filter(|f| f.is_valid(i))
+                .map(|f| f.value(i).to_string()),
+            start_line: start_line.filter(|l| l.is_valid(i)).map(|l| l.value(i)),
+            end_line: end_line.filter(|l| l.is_valid(i)).map(|l| l.value(i)),
+            rerank_score: None,
+        });
+    }
+
+    Ok(())
 }
 
 /// Helper function to Get list of metadata from the record batch based on the column name returns a list of chunks or file names
@@ -244,57 +732,243 @@ fn get_column_data_from_batch(
     Ok(Vec::new())
 }
 
-/// Queries all content from the table, selecting the id, metadata, and content columns.
-/// Returns a stream of record batches containing the queried data.
-async fn query_all_content(table: &Table) -> Result<SendableRecordBatchStream> {
-    let stream = table
+/// Read the rows of the whole table, a page of them if the page size is set
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table to query
+/// * `params` - The search parameters, the filter, offset and page size are used
+/// # Returns
+/// * `Result<Vec<QueryRecord>>` - The rows in table order
+pub async fn query_whole_table(
+    db: &Connection,
+    table_name: &str,
+    params: &QueryParams,
+) -> Result<Vec<QueryRecord>> {
+    let table = open_snapshot(db, table_name, &params.snapshot).await?;
+
+    query_all_content(&table, params).await
+}
+
+/// Plain queries return 10 rows unless a limit is set, the row count is the limit that reads every row
+async fn scan_limit(table: &Table) -> Result<usize> {
+    let rows = table
+        .count_rows(None)
+        .await
+        .context("Failed to count table rows")?;
+    Ok(rows.max(1))
+}
+
+/// The columns of a record every loaded table has
+const RECORD_COLUMNS: [&str; 3] = ["metadata", "chunk_number", "content"];
+/// The columns of a record added after the first tables were loaded, selected when the table has them
+const OPTIONAL_RECORD_COLUMNS: [&str; 4] = ["id", "file_path", "start_line", "end_line"];
+
+/// The record columns to select from the table, Lance fails the query on a column the table does not have
+/// # Arguments
+/// * `table` - The table to read the schema from
+/// * `filter` - The filter of the query, its columns are required
+/// # Returns
+/// * `Result<Vec<String>>` - The columns, an error naming the table to reload if a required column is missing
+async fn record_columns(table: &Table, filter: &Predicate) -> Result<Vec<String>> {
+    let schema = table.schema().await.context("Failed to get table schema")?;
+    let has_column = |column: &&str| schema.field_with_name(column).is_ok();
+
+    // virtual columns like _distance are not in the schema
+    if let Some(column) = RECORD_COLUMNS
+        .iter()
+        .copied()
+        .chain(filter.columns())
+        .filter(|column| !column.starts_with('_'))
+        .find(|column| !has_column(column))
+    {
+        return Err(anyhow!(
+            "Table {} has no {} column, it was loaded by an older version, reload this table",
+            table.name(),
+            column
+        ));
+    }
+
+    Ok(RECORD_COLUMNS
+        .iter()
+        .chain(
+            OPTIONAL_RECORD_COLUMNS
+                .iter()
+                .filter(|column| has_column(column)),
+        )
+        .map(|column| column.to_string())
+        .collect())
+}
+
+/// Queries all content from the table, every batch of the stream is read
+/// One row more than the page size is read to tell if the page is truncated
+async fn query_all_content(table: &Table, params: &QueryParams) -> Result<Vec<QueryRecord>> {
+    let filter = with_filter(Predicate::is_not_null("content"), params);
+    let columns = record_columns(table, &filter).await?;
+    let mut query = table
         .query()
-        .only_if("content IS NOT NULL")
-        .select(lancedb::query::Select::Columns(vec![
-            "id".to_string(),
-            "metadata".to_string(),
-            "content".to_string(),
-        ]))
-        .limit(1000)
+        .only_if(filter.to_string())
+        .select(lancedb::query::Select::Columns(columns))
+        .offset(params.offset);
+    query = match params.page_size {
+        Some(page_size) => query.limit(page_size.saturating_add(1)),
+        None => query.limit(scan_limit(table).await?),
+    };
+    let stream = query
         .execute()
         .await
         .context("Failed to execute whole query and fetch records")?;
-    Ok(stream)
+
+    let mut records = collect_records(stream)
+        .await
+        .context("Failed to get records from record batch")?;
+    if let Some(page_size) = params.page_size {
+        if records.len() > page_size {
+            records.truncate(page_size);
+            warn!(
+                "Whole query of {} truncated to {} rows, use --offset {} for the next page",
+                table.name(),
+                page_size,
+                params.offset + page_size
+            );
+        }
+    }
+    Ok(records)
+}
+
+/// Get the distance metric the vector index of the table was built with
+/// # Arguments
+/// * `table` - The table to read the schema metadata from
+/// # Returns
+/// * `Result<Option<DistanceType>>` - The recorded metric, None for tables indexed before it was recorded
+pub async fn table_distance_type(table: &Table) -> Result<Option<DistanceType>> {
+    let schema = table.schema().await.context("Failed to get table schema")?;
+    schema
+        .metadata()
+        .get(DISTANCE_TYPE_KEY)
+        .map(|d| {
+            DistanceType::try_from(d.as_str())
+                .with_context(|| format!("Unknown distance type in table metadata: {}", d))
+        })
+        .transpose()
+}
+
+/// Get the embedding model the vectors of the table are embedded with
+/// # Arguments
+/// * `table` - The table to read the schema metadata from
+/// # Returns
+/// * `Result<Option<String>>` - The recorded model, None for tables loaded before it was recorded
+pub async fn table_embed_model(table: &Table) -> Result<Option<String>> {
+    let schema = table.schema().await.context("Failed to get table schema")?;
+    Ok(schema.metadata().get(EMBED_MODEL_KEY).cloned())
+}
+
+/// Check the query is embedded with the model of the table since vectors of two models are not comparable
+/// A table that does not record its model is searched for the rows of the query model only
+async fn resolve_embed_model(table: &Table, embed_model: &str) -> Result<Option<Predicate>> {
+    match table_embed_model(table).await? {
+        Some(model) if model != embed_model => Err(anyhow!(
+            "Table {} is embedded with {}, it cannot be queried with {}, set the embedding model to {}",
+            table.name(),
+            model,
+            embed_model,
+            model
+        )),
+        Some(_) => Ok(None),
+        None => {
+            warn!(
+                "Table {} does not record its embedding model, only the rows of {} are searched",
+                table.name(),
+                embed_model
+            );
+            Ok(Some(Predicate::eq("model", embed_model)))
+        }
+    }
+}
+
+/// Check if the table was left without a vector index
+async fn is_flat(table: &Table) -> Result<bool> {
+    let schema = table.schema().await.context("Failed to get table schema")?;
+    Ok(schema
+        .metadata()
+        .get(VECTOR_INDEX_KEY)
+        .is_some_and(|kind| kind == "flat"))
+}
+
+/// Resolve the metric of the query, a requested metric other than the index metric is refused
+/// since the index ranks the vectors by its own metric
+async fn resolve_distance_type(
+    table: &Table,
+    requested: Option<DistanceType>,
+) -> Result<DistanceType> {
+    match (table_distance_type(table).await?, requested) {
+        (Some(indexed), Some(requested)) if indexed != requested => Err(anyhow!(
+            "Table {} is indexed with the {} distance, it cannot be queried with {}",
+            table.name(),
+            indexed,
+            requested
+        )),
+        (Some(indexed), _) => Ok(indexed),
+        (None, requested) => {
+            // the index was built before the metric was recorded, when indices defaulted to L2
+            let distance_type = requested.unwrap_or(DistanceType::L2);
+            warn!(
+                "Table {} does not record the distance type of its index, using {}",
+                table.name(),
+                distance_type
+            );
+            Ok(distance_type)
+        }
+    }
 }
 
 /// Queries the nearest vector to the given query vector.
 /// Returns a stream of record batches containing the queried data.
 /// Arguments:
 /// - query_vector: impl IntoQueryVector + Sized
+/// - embed_model: &str
 /// - table: &Table
+/// - params: &QueryParams
 /// Returns:
-/// - Result<SendableRecordBatchStream>
+/// - Result<(SendableRecordBatchStream, DistanceType)> - the stream and the metric of its distances
 async fn query_nearest_vector(
     query_vector: impl IntoQueryVector + Sized,
+    embed_model: &str,
     table: &Table,
-) -> Result<SendableRecordBatchStream> {
-    let stream: SendableRecordBatchStream = table
+    params: &QueryParams,
+) -> Result<(SendableRecordBatchStream, DistanceType)> {
+    let distance_type = resolve_distance_type(table, params.distance_type).await?;
+    let mut filter = with_filter(Predicate::is_not_null("content"), params);
+    if let Some(model) = resolve_embed_model(table, embed_model).await? {
+        filter = filter.and(model);
+    }
+    let mut columns = record_columns(table, &filter).await?;
+    columns.insert(0, "_distance".to_string());
+    debug!("Querying with {:?} and {} distance", params, distance_type);
+
+    let mut query = table
         .query()
         .nearest_to(query_vector) // Find the nearest vectors to the query vector
-        .context("Failed to select nearest vector")?
+        .context("Failed to select nearest vector")?;
+    // a table reindexed as flat scans the vectors even if an older index is left
+    if is_flat(table).await? {
+        query = query.bypass_vector_index();
+    }
+
+    let stream: SendableRecordBatchStream = query
         // .distance_range(lower_bound, upper_bound) // bug in DataFusion library
-        .distance_type(lancedb::DistanceType::Cosine)
-        .refine_factor(10)
-        .limit(30)
-        .nprobes(40) // default is 20
-        .postfilter()
+        .distance_type(distance_type)
+        .refine_factor(params.refine_factor)
+        .limit(params.limit)
+        .offset(params.offset)
+        .nprobes(params.nprobes) // default is 20
+        // the filter is applied before the search so filtered rows do not take the limit
         // .only_if("_distance > 0.3 AND _distance < 1")
-        .select(lancedb::query::Select::Columns(vec![
-            "_distance".to_string(),
-            "chunk_number".to_string(),
-            "metadata".to_string(),
-            "content".to_string(),
-        ]))
-        .only_if("content IS NOT NULL")
+        .select(lancedb::query::Select::Columns(columns))
+        .only_if(filter.to_string())
         .execute()
         .await
         .context("Failed to execute query and fetch records")?;
-    Ok(stream)
+    Ok((stream, distance_type))
 }
 
 #[allow(dead_code)]
@@ -303,23 +977,23 @@ async fn query_content_based_on_chunks(
     chunks: Vec<String>,
 ) -> Result<SendableRecordBatchStream> {
     // chunk_number in  ["5", "3", "8", "14", "1", "10", "7", "6", "4"]
+    let chunks = chunks
+        .iter()
+        .map(|s| {
+            s.trim()
+                .parse::<i32>()
+                .with_context(|| format!("Invalid chunk number: {:?}", s))
+        })
+        .collect::<Result<Vec<i32>>>()?;
+
+    let filter = Predicate::is_in("chunk_number", chunks);
+    let columns = record_columns(table, &filter).await?;
 
     let stream = table
         .query()
-        .only_if(format!(
-            "chunk_number in ({})",
-            chunks
-                .iter()
-                .map(|s| s.parse::<i32>().unwrap_or(0).to_string())
-                .collect::<Vec<_>>()
-                .join(", ")
-        ))
-        .select(lancedb::query::Select::Columns(vec![
-            "id".to_string(),
-            "metadata".to_string(),
-            "content".to_string(),
-        ]))
-        .limit(1000)
+        .only_if(filter.to_string())
+        .select(lancedb::query::Select::Columns(columns))
+        .limit(scan_limit(table).await?)
         .execute()
         .await
         .context("Failed to execute chunk based query and fetch records")?;
@@ -339,24 +1013,103 @@ async fn query_content_based_on_metadata(
 ) -> Result<SendableRecordBatchStream> {
     // metadata in  ["mod.rs", "cli.rs", "commands.rs", "constants.rs", "main.rs", "lib.rs", "chat_config.rs"]
 
+    let filter = Predicate::is_in("metadata", &metadata);
+    let columns = record_columns(table, &filter).await?;
+
     let stream = table
         .query()
-        .only_if(format!(
-            "metadata IN ({})",
-            metadata
-                .iter()
-                .map(|m| format!("'{}'", m))
-                .collect::<Vec<_>>()
-                .join(", ")
-        ))
-        .select(lancedb::query::Select::Columns(vec![
-            "id".to_string(),
-            "metadata".to_string(),
-            "content".to_string(),
-        ]))
-        .limit(1000)
+        .only_if(filter.to_string())
+        .select(lancedb::query::Select::Columns(columns))
+        .limit(scan_limit(table).await?)
         .execute()
         .await
         .context("Failed to execute chunk based query and fetch records")?;
     Ok(stream)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::RecordBatchIterator;
+    use arrow_schema::{Field, Schema};
+    use std::sync::Arc;
+
+    const ROWS: i32 = 25;
+
+    /// A table of one file with a row per line, more rows than the default limit of a query
+    async fn test_table(name: &str) -> Result<Connection> {
+        let dir = std::env::temp_dir().join(format!("query-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        let db = lancedb::connect(dir.to_str().unwrap()).execute().await?;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", Utf8, false),
+            Field::new("metadata", Utf8, false),
+            Field::new("chunk_number", Int32, true),
+            Field::new("content", Utf8, false),
+            Field::new("file_path", Utf8, true),
+            Field::new("start_line", Int32, true),
+            Field::new("end_line", Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    (0..ROWS).map(|i| i.to_string()),
+                )),
+                Arc::new(StringArray::from_iter_values((0..ROWS).map(|_| "main.rs"))),
+                Arc::new(Int32Array::from_iter_values((0..ROWS).map(|i| i / 5))),
+                Arc::new(StringArray::from_iter_values(
+                    (0..ROWS).map(|i| format!("line {}", i)),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    (0..ROWS).map(|_| "src/main.rs"),
+                )),
+                Arc::new(Int32Array::from_iter_values(1..=ROWS)),
+                Arc::new(Int32Array::from_iter_values(1..=ROWS)),
+            ],
+        )?;
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        db.create_table(name, Box::new(reader)).execute().await?;
+        Ok(db)
+    }
+
+    #[tokio::test]
+    async fn whole_table_query_reads_every_row() -> Result<()> {
+        let db = test_table("whole").await?;
+        let records = query_whole_table(&db, "whole", &QueryParams::default()).await?;
+        assert_eq!(records.len(), ROWS as usize);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn whole_table_query_pages() -> Result<()> {
+        let db = test_table("paged").await?;
+        let params = QueryParams {
+            page_size: Some(10),
+            offset: 20,
+            ..QueryParams::default()
+        };
+        let records = query_whole_table(&db, "paged", &params).await?;
+        assert_eq!(records.len(), 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn file_context_reads_every_row_of_the_file() -> Result<()> {
+        let db = test_table("context").await?;
+        let table = db.open_table("context").execute().await?;
+        let found = query_whole_table(
+            &db,
+            "context",
+            &QueryParams {
+                page_size: Some(1),
+                ..QueryParams::default()
+            },
+        )
+        .await?;
+        let records = query_file_context(&table, found).await?;
+        assert_eq!(records.len(), ROWS as usize);
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use crate::app::config::app_data_dir;
+use crate::app::constants::SOURCES_FILE;
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// SourceEntry is a source directory loaded into a table, stored in `<data dir>/sources.json`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SourceEntry {
+    /// The name of the source stored in the source column
+    pub source: String,
+    /// The loaded directory
+    pub path: String,
+    pub database: String,
+    pub table: String,
+    pub model: String,
+    /// The number of chunks loaded from the source
+    pub chunks: usize,
+    pub loaded_at: String,
+}
+
+fn registry_path() -> Result<PathBuf> {
+    Ok(app_data_dir()?.join(SOURCES_FILE))
+}
+
+/// Load the registered sources, empty if nothing was loaded yet
+pub async fn load_sources() -> Result<Vec<SourceEntry>> {
+    let path = registry_path()?;
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read source registry {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse source registry {}", path.display()))
+}
+
+async fn save_sources(sources: &[SourceEntry]) -> Result<()> {
+    let path = registry_path()?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+    let content =
+        serde_json::to_string_pretty(sources).context("Failed to serialize source registry")?;
+    tokio::fs::write(&path, content)
+        .await
+        .with_context(|| format!("Failed to write source registry {}", path.display()))?;
+    debug!("Saved {} sources to {}", sources.len(), path.display());
+    Ok(())
+}
+
+/// Record a loaded source, a source loaded again into the same table replaces its entry
+/// # Arguments
+/// * `entry` - The loaded source
+/// * `append` - If false the table was recreated and the other sources of the table are removed
+pub async fn record_source(entry: SourceEntry, append: bool) -> Result<()> {
+    let mut sources = load_sources().await?;
+    sources.retain(|s| {
+        let same_table = s.database == entry.database && s.table == entry.table;
+        !same_table || (append && s.source != entry.source)
+    });
+    sources.push(entry);
+    save_sources(&sources).await
+}
+
+/// The registered sources of the database, all sources if no database is given
+pub async fn list_sources(database: Option<&str>) -> Result<Vec<SourceEntry>> {
+    let sources = load_sources().await?;
+    Ok(sources
+        .into_iter()
+        .filter(|s| database.is_none_or(|d| s.database == d))
+        .collect())
+}
+
+/// Remove the sources of a dropped table
+pub async fn forget_table(database: &str, table: &str) -> Result<()> {
+    let mut sources = load_sources().await?;
+    sources.retain(|s| !(s.database == database && s.table == table));
+    save_sources(&sources).await
+}
+
+/// Move the sources of a renamed table to the new name
+pub async fn rename_table(database: &str, table: &str, new_name: &str) -> Result<()> {
+    let mut sources = load_sources().await?;
+    for source in sources
+        .iter_mut()
+        .filter(|s| s.database == database && s.table == table)
+    {
+        source.table = new_name.to_string();
+    }
+    save_sources(&sources).await
+}
@@ -0,0 +1,335 @@
+use crate::lancevectordb::predicate::Predicate;
+use anyhow::{anyhow, Context, Result};
+use arrow_array::{Array, StringArray, TimestampSecondArray};
+use arrow_schema::DataType;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use lancedb::index::IndexType;
+use lancedb::query::{ExecutableQuery, QueryBase};
+use lancedb::{Connection, DistanceType, Table};
+use log::{debug, info};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// ColumnInfo is a field of the table schema
+#[derive(Debug, Clone)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+/// IndexInfo is an index of the table with the rows it covers
+#[derive(Debug, Clone)]
+pub struct IndexInfo {
+    pub name: String,
+    pub index_type: IndexType,
+    pub columns: Vec<String>,
+    pub indexed_rows: Option<usize>,
+    pub unindexed_rows: Option<usize>,
+    pub distance_type: Option<DistanceType>,
+}
+
+/// TableDescription is the schema, schema metadata and indices of a table
+#[derive(Debug, Clone)]
+pub struct TableDescription {
+    pub name: String,
+    pub uri: String,
+    pub version: u64,
+    pub columns: Vec<ColumnInfo>,
+    pub metadata: HashMap<String, String>,
+    pub indices: Vec<IndexInfo>,
+}
+
+/// TableStats summarizes the rows loaded into a table
+#[derive(Debug, Clone)]
+pub struct TableStats {
+    pub name: String,
+    pub rows: usize,
+    pub files: usize,
+    pub models: Vec<String>,
+    pub dimension: Option<i32>,
+    pub first_created: Option<DateTime<Utc>>,
+    pub last_created: Option<DateTime<Utc>>,
+    /// Size of the table directory, None for tables that are not on the local disk
+    pub size_bytes: Option<u64>,
+}
+
+/// List the tables of the database
+/// # Arguments
+/// * `db` - The database connection
+/// # Returns
+/// * `Result<Vec<String>>` - The table names in name order
+pub async fn list_tables(db: &Connection) -> Result<Vec<String>> {
+    db.table_names()
+        .execute()
+        .await
+        .with_context(|| format!("Failed to list the tables of {}", db.uri()))
+}
+
+async fn open_table(db: &Connection, table_name: &str) -> Result<Table> {
+    db.open_table(table_name)
+        .execute()
+        .await
+        .with_context(|| format!("Failed to open table {}", table_name))
+}
+
+/// Describe the schema and the index status of a table
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table
+/// # Returns
+/// * `Result<TableDescription>` - The columns, schema metadata and indices of the table
+pub async fn describe_table(db: &Connection, table_name: &str) -> Result<TableDescription> {
+    let table = open_table(db, table_name).await?;
+    let schema = table.schema().await.context("Failed to get table schema")?;
+    let version = table
+        .version()
+        .await
+        .context("Failed to get table version")?;
+
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| ColumnInfo {
+            name: field.name().to_string(),
+            data_type: field.data_type().to_string(),
+            nullable: field.is_nullable(),
+        })
+        .collect();
+
+    let mut indices = Vec::new();
+    for index in table
+        .list_indices()
+        .await
+        .context("Failed to list table indices")?
+    {
+        let stats = table
+            .index_stats(&index.name)
+            .await
+            .with_context(|| format!("Failed to get statistics of index {}", index.name))?;
+        indices.push(IndexInfo {
+            name: index.name,
+            index_type: index.index_type,
+            columns: index.columns,
+            indexed_rows: stats.as_ref().map(|s| s.num_indexed_rows),
+            unindexed_rows: stats.as_ref().map(|s| s.num_unindexed_rows),
+            distance_type: stats.and_then(|s| s.distance_type),
+        });
+    }
+
+    Ok(TableDescription {
+        name: table_name.to_string(),
+        uri: table.dataset_uri().to_string(),
+        version,
+        columns,
+        metadata: schema.metadata().clone(),
+        indices,
+    })
+}
+
+/// Count the rows, files and models of a table
+/// The metadata, model and created_at columns are read batch by batch
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table
+/// # Returns
+/// * `Result<TableStats>` - The statistics of the table
+pub async fn table_stats(db: &Connection, table_name: &str) -> Result<TableStats> {
+    let table = open_table(db, table_name).await?;
+    let schema = table.schema().await.context("Failed to get table schema")?;
+    let rows = table
+        .count_rows(None)
+        .await
+        .context("Failed to count table rows")?;
+
+    let dimension =
+        schema
+            .field_with_name("vector")
+            .ok()
+            .and_then(|field| match field.data_type() {
+                DataType::FixedSizeList(_, size) => Some(*size),
+                _ => None,
+            });
+
+    // tables loaded before the file path was stored only have the file name
+    let has_file_path = schema.field_with_name("file_path").is_ok();
+    let mut columns = vec![
+        "metadata".to_string(),
+        "model".to_string(),
+        "created_at".to_string(),
+    ];
+    if has_file_path {
+        columns.push("file_path".to_string());
+    }
+
+    let mut stream = table
+        .query()
+        .select(lancedb::query::Select::Columns(columns))
+        // plain queries return 10 rows unless a limit is set
+        .limit(rows.max(1))
+        .execute()
+        .await
+        .context("Failed to execute stats query and fetch records")?;
+
+    let mut files = BTreeSet::new();
+    let mut models = BTreeSet::new();
+    let mut first_created: Option<i64> = None;
+    let mut last_created: Option<i64> = None;
+    while let Some(batch) = stream.next().await {
+        let batch = batch.map_err(|e| anyhow!(format!("Failed to get RecordBatch: {}", e)))?;
+        let metadata = batch
+            .column_by_name("metadata")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+        let file_path = batch
+            .column_by_name("file_path")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+        let model = batch
+            .column_by_name("model")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+        let created_at = batch
+            .column_by_name("created_at")
+            .and_then(|c| c.as_any().downcast_ref::<TimestampSecondArray>());
+
+        for i in 0..batch.num_rows() {
+            let path = file_path
+                .filter(|f| f.is_valid(i))
+                .or(metadata.filter(|m| m.is_valid(i)))
+                .map(|a| a.value(i));
+            if let Some(path) = path {
+                if !files.contains(path) {
+                    files.insert(path.to_string());
+                }
+            }
+            if let Some(model) = model.filter(|m| m.is_valid(i)).map(|m| m.value(i)) {
+                if !models.contains(model) {
+                    models.insert(model.to_string());
+                }
+            }
+            if let Some(created) = created_at.filter(|c| c.is_valid(i)).map(|c| c.value(i)) {
+                first_created = Some(first_created.map_or(created, |t| t.min(created)));
+                last_created = Some(last_created.map_or(created, |t| t.max(created)));
+            }
+        }
+    }
+
+    let size_bytes = table_size(table.dataset_uri())?;
+
+    Ok(TableStats {
+        name: table_name.to_string(),
+        rows,
+        files: files.len(),
+        models: models.into_iter().collect(),
+        dimension,
+        first_created: first_created.and_then(|t| DateTime::from_timestamp(t, 0)),
+        last_created: last_created.and_then(|t| DateTime::from_timestamp(t, 0)),
+        size_bytes,
+    })
+}
+
+/// Remove the placeholder rows older versions inserted when a table was created
+/// The 256 placeholder rows have an empty content, file name and model, real rows always have a file name and model
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table to clean up
+/// # Returns
+/// * `Result<usize>` - The number of removed rows
+pub async fn remove_placeholder_rows(db: &Connection, table_name: &str) -> Result<usize> {
+    let table = open_table(db, table_name).await?;
+    let placeholder = Predicate::eq("content", "")
+        .and(Predicate::eq("metadata", ""))
+        .and(Predicate::eq("model", ""))
+        .to_string();
+
+    let rows = table
+        .count_rows(Some(placeholder.clone()))
+        .await
+        .context("Failed to count the placeholder rows")?;
+    if rows > 0 {
+        table
+            .delete(&placeholder)
+            .await
+            .with_context(|| format!("Failed to delete the placeholder rows of {}", table_name))?;
+    }
+
+    info!("Removed {} placeholder rows from {}", rows, table_name);
+    Ok(rows)
+}
+
+/// Drop the table and its data
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table to drop
+pub async fn drop_table(db: &Connection, table_name: &str) -> Result<()> {
+    // open the table first so a missing table is reported as missing
+    open_table(db, table_name).await?;
+    db.drop_table(table_name)
+        .await
+        .with_context(|| format!("Failed to drop table {}", table_name))?;
+    info!("Dropped table {}", table_name);
+    Ok(())
+}
+
+/// Rename the table
+/// LanceDB only renames tables in the cloud, a local table directory is moved instead
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table to rename
+/// * `new_name` - The new name of the table
+pub async fn rename_table(db: &Connection, table_name: &str, new_name: &str) -> Result<()> {
+    open_table(db, table_name).await?;
+    if list_tables(db).await?.iter().any(|t| t == new_name) {
+        return Err(anyhow!("Table {} already exists", new_name));
+    }
+
+    match db.rename_table(table_name, new_name).await {
+        Ok(()) => {}
+        Err(lancedb::Error::NotSupported { .. }) => {
+            let base = local_path(db.uri())
+                .ok_or_else(|| anyhow!("Renaming tables of {} is not supported", db.uri()))?;
+            let from = base.join(format!("{}.lance", table_name));
+            let to = base.join(format!("{}.lance", new_name));
+            debug!("Moving {} to {}", from.display(), to.display());
+            std::fs::rename(&from, &to).with_context(|| {
+                format!("Failed to move {} to {}", from.display(), to.display())
+            })?;
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to rename table {}", table_name)),
+    }
+
+    info!("Renamed table {} to {}", table_name, new_name);
+    Ok(())
+}
+
+/// The local path of a database or table URI, None for object store URIs
+fn local_path(uri: &str) -> Option<PathBuf> {
+    match uri.split_once("://") {
+        Some(("file", path)) => Some(PathBuf::from(path)),
+        Some(_) => None,
+        None => Some(PathBuf::from(uri)),
+    }
+}
+
+/// Size of the table directory, None for tables that are not on the local disk
+pub(crate) fn table_size(uri: &str) -> Result<Option<u64>> {
+    local_path(uri)
+        .filter(|path| path.exists())
+        .map(|path| directory_size(&path))
+        .transpose()
+        .context("Failed to read the size of the table directory")
+}
+
+/// Total size of the files below the directory
+fn directory_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            directory_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use lancedb::{Connection, Table};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Lines of a file in one chunk of the test rows
+pub(crate) const LINES_PER_CHUNK: i32 = 5;
+
+/// A database in its own temp directory, emptied if an earlier run left it behind
+pub(crate) async fn temp_db(name: &str) -> Result<Connection> {
+    let dir =
+        std::env::temp_dir().join(format!("vector-embed-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    Ok(lancedb::connect(dir.to_str().unwrap()).execute().await?)
+}
+
+/// The rows of a file with a row per line, the columns a query reads
+/// # Arguments
+/// * `file_path` - The path stored with the rows, the file name is the metadata
+/// * `lines` - The line numbers, chunk n holds the lines from n * 5 + 1
+pub(crate) fn file_rows(file_path: &str, lines: Range<i32>) -> Result<RecordBatch> {
+    let file_name = Path::new(file_path)
+        .file_name()
+        .map_or(file_path.to_string(), |n| n.to_string_lossy().to_string());
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("metadata", DataType::Utf8, false),
+        Field::new("chunk_number", DataType::Int32, true),
+        Field::new("content", DataType::Utf8, false),
+        Field::new("file_path", DataType::Utf8, true),
+        Field::new("start_line", DataType::Int32, true),
+        Field::new("end_line", DataType::Int32, true),
+    ]));
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from_iter_values(
+                lines.clone().map(|l| format!("{}:{}", file_path, l)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                lines.clone().map(|_| file_name.as_str()),
+            )),
+            Arc::new(Int32Array::from_iter_values(
+                lines.clone().map(|l| (l - 1) / LINES_PER_CHUNK),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                lines.clone().map(|l| format!("{} line {}", file_path, l)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                lines.clone().map(|_| file_path),
+            )),
+            Arc::new(Int32Array::from_iter_values(lines.clone())),
+            Arc::new(Int32Array::from_iter_values(lines)),
+        ],
+    )?)
+}
+
+/// Create a table with the rows of the files
+pub(crate) async fn create_file_table(
+    db: &Connection,
+    name: &str,
+    files: &[(&str, Range<i32>)],
+) -> Result<Table> {
+    let batches = files
+        .iter()
+        .map(|(path, lines)| file_rows(path, lines.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    let schema = batches[0].schema();
+    let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema);
+    Ok(db.create_table(name, Box::new(reader)).execute().await?)
+}
+
+/// Append the rows of a file to the table, every append is a new version
+pub(crate) async fn add_file_rows(table: &Table, file_path: &str, lines: Range<i32>) -> Result<()> {
+    let batch = file_rows(file_path, lines)?;
+    let schema = batch.schema();
+    let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+    table.add(Box::new(reader)).execute().await?;
+    Ok(())
+}
@@ -0,0 +1,322 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use lance::Dataset;
+use lancedb::{Connection, Table};
+use log::{debug, info};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::process::Command;
+
+/// Snapshot is the table version a query reads, the latest version unless a version or tag is given
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Snapshot {
+    #[default]
+    Latest,
+    Version(u64),
+    Tag(String),
+}
+
+impl Snapshot {
+    /// The snapshot of the `--version` and `--tag` options, giving both is an error
+    pub fn from_args(version: Option<u64>, tag: Option<String>) -> Result<Snapshot> {
+        match (version, tag) {
+            (Some(_), Some(_)) => Err(anyhow!("Use either --version or --tag, not both")),
+            (Some(version), None) => Ok(Snapshot::Version(version)),
+            (None, Some(tag)) => Ok(Snapshot::Tag(tag)),
+            (None, None) => Ok(Snapshot::Latest),
+        }
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Snapshot::Latest => write!(f, "latest"),
+            Snapshot::Version(version) => write!(f, "version {}", version),
+            Snapshot::Tag(tag) => write!(f, "tag {}", tag),
+        }
+    }
+}
+
+/// VersionInfo is a version of a table with the tags pointing to it
+#[derive(Debug, Clone)]
+pub struct VersionInfo {
+    pub version: u64,
+    pub timestamp: DateTime<Utc>,
+    pub tags: Vec<String>,
+    pub latest: bool,
+}
+
+/// Open the Lance dataset of the table, the tags are Lance refs stored next to the versions
+async fn dataset(table: &Table) -> Result<Dataset> {
+    Dataset::open(table.dataset_uri())
+        .await
+        .with_context(|| format!("Failed to open the dataset of table {}", table.name()))
+}
+
+/// The tags of a table, Lance tags so pruning keeps the tagged versions and other Lance tools see them
+/// # Arguments
+/// * `table` - The table
+/// # Returns
+/// * `Result<BTreeMap<String, u64>>` - The version of every tag
+pub async fn list_tags(table: &Table) -> Result<BTreeMap<String, u64>> {
+    let tags = dataset(table)
+        .await?
+        .tags
+        .list()
+        .await
+        .context("Failed to list the table tags")?;
+    Ok(tags
+        .into_iter()
+        .map(|(tag, contents)| (tag, contents.version))
+        .collect())
+}
+
+/// Open the table at the version of the snapshot
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table
+/// * `snapshot` - The version to read
+/// # Returns
+/// * `Result<Table>` - The table, read only unless the snapshot is the latest version
+pub async fn open_snapshot(
+    db: &Connection,
+    table_name: &str,
+    snapshot: &Snapshot,
+) -> Result<Table> {
+    let table = db
+        .open_table(table_name)
+        .execute()
+        .await
+        .with_context(|| format!("Failed to open table {}", table_name))?;
+
+    let version = match snapshot {
+        Snapshot::Latest => return Ok(table),
+        Snapshot::Version(version) => *version,
+        Snapshot::Tag(tag) => dataset(&table)
+            .await?
+            .tags
+            .get_version(tag)
+            .await
+            .with_context(|| format!("Table {} has no tag {}", table_name, tag))?,
+    };
+    debug!(
+        "Checking out {} of table {} as version {}",
+        snapshot, table_name, version
+    );
+    table
+        .checkout(version)
+        .await
+        .with_context(|| format!("Failed to check out version {} of {}", version, table_name))?;
+    Ok(table)
+}
+
+/// List the versions of a table with their tags, oldest first
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table
+/// # Returns
+/// * `Result<Vec<VersionInfo>>` - The versions that were not pruned
+pub async fn list_versions(db: &Connection, table_name: &str) -> Result<Vec<VersionInfo>> {
+    let table = open_snapshot(db, table_name, &Snapshot::Latest).await?;
+    let tags = list_tags(&table).await?;
+    let mut versions = table
+        .list_versions()
+        .await
+        .context("Failed to list the table versions")?;
+    versions.sort_by_key(|v| v.version);
+    let latest = versions.last().map(|v| v.version);
+
+    Ok(versions
+        .into_iter()
+        .map(|v| VersionInfo {
+            version: v.version,
+            timestamp: v.timestamp,
+            tags: tags
+                .iter()
+                .filter(|(_, version)| **version == v.version)
+                .map(|(tag, _)| tag.clone())
+                .collect(),
+            latest: Some(v.version) == latest,
+        })
+        .collect())
+}
+
+/// Tag a version of the table, a tag given again is moved to the new version
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table
+/// * `tag` - The name of the tag, like the git commit of the loaded source
+/// * `version` - The version to tag, the latest version if None
+/// # Returns
+/// * `Result<u64>` - The tagged version
+pub async fn tag_version(
+    db: &Connection,
+    table_name: &str,
+    tag: &str,
+    version: Option<u64>,
+) -> Result<u64> {
+    if tag.is_empty() {
+        return Err(anyhow!("The tag must not be empty"));
+    }
+    let table = open_snapshot(db, table_name, &Snapshot::Latest).await?;
+    let versions = table
+        .list_versions()
+        .await
+        .context("Failed to list the table versions")?;
+    let version = match version {
+        Some(version) if versions.iter().any(|v| v.version == version) => version,
+        Some(version) => return Err(anyhow!("Table {} has no version {}", table_name, version)),
+        None => table
+            .version()
+            .await
+            .context("Failed to get table version")?,
+    };
+
+    // tagging does not write a table version, the tag is a ref file next to the versions
+    let mut dataset = dataset(&table).await?;
+    let moved = dataset
+        .tags
+        .list()
+        .await
+        .context("Failed to list the table tags")?
+        .contains_key(tag);
+    let saved = match moved {
+        true => dataset.tags.update(tag, version).await,
+        false => dataset.tags.create(tag, version).await,
+    };
+    saved.with_context(|| format!("Failed to tag version {} as {}", version, tag))?;
+    info!("Tagged version {} of {} as {}", version, table_name, tag);
+    Ok(version)
+}
+
+/// Remove a tag of the table, the tagged version is pruned once it is older than the retention
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table
+/// * `tag` - The name of the tag
+/// # Returns
+/// * `Result<u64>` - The version the tag pointed to
+pub async fn untag_version(db: &Connection, table_name: &str, tag: &str) -> Result<u64> {
+    let table = open_snapshot(db, table_name, &Snapshot::Latest).await?;
+    let mut dataset = dataset(&table).await?;
+    let version = dataset
+        .tags
+        .get_version(tag)
+        .await
+        .with_context(|| format!("Table {} has no tag {}", table_name, tag))?;
+
+    dataset
+        .tags
+        .delete(tag)
+        .await
+        .with_context(|| format!("Failed to remove tag {}", tag))?;
+    info!(
+        "Removed tag {} of version {} of {}",
+        tag, version, table_name
+    );
+    Ok(version)
+}
+
+/// The short commit of the git checkout the path is in, None if it is not in a checkout
+pub fn git_commit(path: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["-C", path, "rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !commit.is_empty()).then_some(commit)
+}
+
+/// Restore an old version of the table as the latest version
+/// The restored version is written as a new version so the versions in between are kept
+/// # Arguments
+/// * `db` - The database connection
+/// * `table_name` - The name of the table
+/// * `snapshot` - The version or tag to restore
+/// # Returns
+/// * `Result<u64>` - The new latest version
+pub async fn restore_version(
+    db: &Connection,
+    table_name: &str,
+    snapshot: &Snapshot,
+) -> Result<u64> {
+    if *snapshot == Snapshot::Latest {
+        return Err(anyhow!("Give the version or tag to restore"));
+    }
+    // the tags are refs outside the versions, restoring does not change them
+    let table = open_snapshot(db, table_name, snapshot).await?;
+    table
+        .restore()
+        .await
+        .with_context(|| format!("Failed to restore {} of {}", snapshot, table_name))?;
+
+    let version = table
+        .version()
+        .await
+        .context("Failed to get table version")?;
+    info!(
+        "Restored {} of {} as version {}",
+        snapshot, table_name, version
+    );
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lancevectordb::test_tables::{add_file_rows, create_file_table, temp_db};
+
+    #[tokio::test]
+    async fn tag_checkout_restore_round_trip() -> Result<()> {
+        let db = temp_db("versions").await?;
+        let table = create_file_table(&db, "versions", &[("src/main.rs", 1..11)]).await?;
+        let tagged = tag_version(&db, "versions", "first", None).await?;
+        add_file_rows(&table, "src/lib.rs", 1..6).await?;
+
+        // a tag is a ref next to the versions, tagging does not write a version
+        let versions = list_versions(&db, "versions").await?;
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].tags, vec!["first".to_string()]);
+        assert_eq!(list_tags(&table).await?.get("first"), Some(&tagged));
+
+        let first = open_snapshot(&db, "versions", &Snapshot::Tag("first".to_string())).await?;
+        assert_eq!(first.count_rows(None).await?, 10);
+        let latest = open_snapshot(&db, "versions", &Snapshot::Latest).await?;
+        assert_eq!(latest.count_rows(None).await?, 15);
+
+        let restored =
+            restore_version(&db, "versions", &Snapshot::Tag("first".to_string())).await?;
+        assert_eq!(restored, versions[1].version + 1);
+        let latest = open_snapshot(&db, "versions", &Snapshot::Latest).await?;
+        assert_eq!(latest.count_rows(None).await?, 10);
+        assert_eq!(list_tags(&latest).await?.get("first"), Some(&tagged));
+
+        assert_eq!(untag_version(&db, "versions", "first").await?, tagged);
+        assert!(list_tags(&latest).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_tags_are_errors() -> Result<()> {
+        let db = temp_db("missing-tag").await?;
+        create_file_table(&db, "missing", &[("src/main.rs", 1..3)]).await?;
+        assert!(
+            open_snapshot(&db, "missing", &Snapshot::Tag("none".to_string()))
+                .await
+                .is_err()
+        );
+        assert!(untag_version(&db, "missing", "none").await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn version_and_tag_are_exclusive() {
+        assert!(Snapshot::from_args(Some(1), Some("v1".to_string())).is_err());
+        assert_eq!(
+            Snapshot::from_args(None, Some("v1".to_string())).unwrap(),
+            Snapshot::Tag("v1".to_string())
+        );
+        assert_eq!(Snapshot::from_args(None, None).unwrap(), Snapshot::Latest);
+    }
+}